@@ -1,11 +1,75 @@
-use std::io::{self, BufRead};
+use aoc_2018::{parse_steps, ParseStepError};
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader},
+    process,
+};
+
+fn sum_frequencies<I: IntoIterator<Item = i32>>(steps: I) -> i32 {
+    steps.into_iter().sum()
+}
+
+/// Returns the frequency after each step.
+fn running_frequencies(steps: &[i32]) -> Vec<i32> {
+    steps
+        .iter()
+        .scan(0, |current, step| {
+            *current += step;
+            Some(*current)
+        })
+        .collect()
+}
+
+/// Reads the steps from the given file or, if there is none, stdin.
+fn read_steps(path: Option<&str>) -> Result<Vec<i32>, ParseStepError> {
+    match path {
+        Some(path) => parse_steps(BufReader::new(File::open(path)?)),
+        None => parse_steps(io::stdin().lock()),
+    }
+}
 
 fn main() {
-    let stdin = io::stdin();
-    let stdin = stdin.lock();
-    let freq_steps = stdin
-        .lines()
-        .map(|line| line.unwrap().trim().parse::<i32>().unwrap());
-    let sum = freq_steps.sum::<i32>();
+    let (flags, paths): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let trace = flags.iter().any(|flag| flag == "--trace");
+    let freq_steps = read_steps(paths.first().map(String::as_str)).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    });
+    if trace {
+        for current in running_frequencies(&freq_steps) {
+            println!("{}", current);
+        }
+    }
+    let sum = sum_frequencies(freq_steps);
     println!("sum: {}", sum);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sum_frequencies() {
+        assert_eq!(sum_frequencies(vec![]), 0);
+        assert_eq!(sum_frequencies(vec![-7]), -7);
+        assert_eq!(sum_frequencies(vec![1, -2, 3, 1]), 3);
+        assert_eq!(sum_frequencies(vec![-1, -2, -3]), -6);
+    }
+
+    #[test]
+    fn test_blank_lines_and_signs() {
+        let input = "+3\n\n-1\n  \n+7\r\n-2\n\n";
+        let steps = parse_steps(input.as_bytes()).unwrap();
+        assert_eq!(steps, [3, -1, 7, -2]);
+        assert_eq!(sum_frequencies(steps), 7);
+    }
+
+    #[test]
+    fn test_running_frequencies() {
+        assert_eq!(running_frequencies(&[1, 1, 1]), [1, 2, 3]);
+        assert_eq!(running_frequencies(&[1, -2, 3, 1]), [1, -1, 2, 3]);
+        assert_eq!(running_frequencies(&[]), []);
+    }
+}
@@ -1,9 +1,11 @@
 use std::{
     collections::HashMap,
+    env,
     fmt::{self, Display},
     io::{self, BufRead},
     num::ParseIntError,
     ops::{Add, Sub},
+    process,
     str::FromStr,
 };
 
@@ -28,7 +30,7 @@ impl Point {
 
 impl Display for Point {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "({}, {})", self.x, self.y)
+        write!(f, "{}, {}", self.x, self.y)
     }
 }
 
@@ -47,12 +49,15 @@ impl From<ParseIntError> for ParsePointError {
 impl FromStr for Point {
     type Err = ParsePointError;
 
+    /// Parses the format written by `Display`, e.g. `3, 4`.
+    ///
+    /// Whitespace around either coordinate is ignored.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         use self::ParsePointError::*;
 
-        let mut coords = s.splitn(2, ",");
-        let x = coords.next().ok_or(BadCoords)?.trim_end().parse()?;
-        let y = coords.next().ok_or(BadCoords)?.trim_start().parse()?;
+        let mut coords = s.splitn(2, ',');
+        let x = coords.next().ok_or(BadCoords)?.trim().parse()?;
+        let y = coords.next().ok_or(BadCoords)?.trim().parse()?;
         debug_assert!(coords.next().is_none());
         Ok(Point { x, y })
     }
@@ -80,6 +85,16 @@ impl Sub<(i32, i32)> for Point {
     }
 }
 
+/// Iterates over all points within Manhattan distance `radius` of
+/// `center`, column by column.
+#[allow(dead_code)]
+fn points_within(center: Point, radius: i32) -> impl Iterator<Item = Point> {
+    (-radius..=radius).flat_map(move |dx| {
+        let rest = radius - dx.abs();
+        (-rest..=rest).map(move |dy| center + (dx, dy))
+    })
+}
+
 struct Board {
     top_left: Point,
     bottom_right: Point,
@@ -107,6 +122,26 @@ impl Board {
         self.bottom_right.y = cmp::max(self.bottom_right.y, p.y + 1);
     }
 
+    fn width(&self) -> usize {
+        (self.bottom_right.x - self.top_left.x + 1) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.bottom_right.y - self.top_left.y + 1) as usize
+    }
+
+    fn contains(&self, p: Point) -> bool {
+        (self.top_left.x..=self.bottom_right.x).contains(&p.x)
+            && (self.top_left.y..=self.bottom_right.y).contains(&p.y)
+    }
+
+    /// Returns the position of `p` in the order of `iter()`.
+    fn cell_index(&self, p: Point) -> usize {
+        debug_assert!(self.contains(p));
+        let (dx, dy) = (p.x - self.top_left.x, p.y - self.top_left.y);
+        dx as usize * self.height() + dy as usize
+    }
+
     fn iter(&self) -> impl Iterator<Item = Point> {
         let (min, max) = (self.top_left, self.bottom_right);
         (min.x..=max.x).flat_map(move |x| (min.y..=max.y).map(move |y| Point { x, y }))
@@ -136,40 +171,55 @@ impl std::iter::FromIterator<Point> for Option<Board> {
     }
 }
 
+/// How to pick the nearest point if several are equally near.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TieBreak {
+    /// None of them is the nearest point.
+    NoOwner,
+    /// The lowest point, ordered by x first, then y, is the nearest.
+    #[allow(dead_code)]
+    Lowest,
+}
+
 fn find_nearest_point(refp: Point, candidates: impl IntoIterator<Item = Point>) -> Option<Point> {
+    find_nearest_point_with(refp, candidates, TieBreak::NoOwner)
+}
+
+fn find_nearest_point_with(
+    refp: Point,
+    candidates: impl IntoIterator<Item = Point>,
+    tie_break: TieBreak,
+) -> Option<Point> {
+    use std::cmp::{min, Ordering};
+
+    // The best distance so far, the winning candidate, and whether
+    // another candidate is equally near.
     let mut nearest = None;
-    let mut best_distance = None;
     for candidate in candidates {
         let distance = candidate.distance(refp);
-        match (distance, best_distance) {
-            (d, None) => {
-                // No best distance yet, automatic win.
-                nearest = Some(candidate);
-                best_distance = Some(d);
-            },
-            (d, Some(bd)) if d < bd => {
-                // Better than the previously best candidate, replace it.
-                nearest = Some(candidate);
-                best_distance = Some(d);
+        nearest = match nearest {
+            None => Some((distance, candidate, false)),
+            Some((best_distance, best, tied)) => match distance.cmp(&best_distance) {
+                Ordering::Less => Some((distance, candidate, false)),
+                Ordering::Equal => Some((best_distance, min(best, candidate), true)),
+                Ordering::Greater => Some((best_distance, best, tied)),
             },
-            (d, Some(bd)) if d == bd => {
-                // Tie, no one wins, but the next candidate must still
-                // be better than the currently best distance.
-                nearest = None;
-            },
-            _ => {},
-        }
+        };
+    }
+    match (nearest?, tie_break) {
+        ((_, _, true), TieBreak::NoOwner) => None,
+        ((_, nearest, _), _) => Some(nearest),
     }
-    nearest
 }
 
 
+#[allow(dead_code)]
 fn find_largest_area(coords: impl IntoIterator<Item = Point>) -> Option<(Point, usize)> {
     let mut coords = coords
         .into_iter()
         .map(|p| (p, Some(0)))
         .collect::<HashMap<Point, Option<usize>>>();
-    let board = coords.keys().cloned().collect::<Option<Board>>().unwrap();
+    let board = coords.keys().cloned().collect::<Option<Board>>()?;
     for point in board.iter() {
         if let Some(coord) = find_nearest_point(point, coords.keys().cloned()) {
             if point.is_on_edge(&board) {
@@ -185,17 +235,192 @@ fn find_largest_area(coords: impl IntoIterator<Item = Point>) -> Option<(Point,
         .max_by_key(|&(_, c)| c)
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Owner {
+    Site(usize),
+    Tie,
+}
+
+/// Finds the nearest site of every cell on the board.
+///
+/// This floods the board from all sites at once, one step of distance
+/// at a time. A cell reached from two different sites in the same step
+/// is a tie. Since there are no obstacles, the number of steps is the
+/// Manhattan distance.
+///
+/// The result has one entry per cell in the order of `Board::iter()`.
+/// Sites are identified by their index in `sites`, which must not
+/// contain duplicates.
+fn flood_owners(sites: &[Point], board: &Board) -> Vec<Owner> {
+    let mut owners = vec![None; board.width() * board.height()];
+    let mut distances = vec![0; owners.len()];
+    let mut frontier = Vec::with_capacity(sites.len());
+    for (i, &site) in sites.iter().enumerate() {
+        owners[board.cell_index(site)] = Some(Owner::Site(i));
+        frontier.push(site);
+    }
+    let mut distance = 0;
+    while !frontier.is_empty() {
+        distance += 1;
+        let mut next = Vec::new();
+        for cell in frontier {
+            let owner = owners[board.cell_index(cell)];
+            for &step in [(1, 0), (-1, 0), (0, 1), (0, -1)].iter() {
+                let neighbor = cell + step;
+                if !board.contains(neighbor) {
+                    continue;
+                }
+                let i = board.cell_index(neighbor);
+                match owners[i] {
+                    None => {
+                        owners[i] = owner;
+                        distances[i] = distance;
+                        next.push(neighbor);
+                    },
+                    Some(_) if distances[i] == distance && owners[i] != owner => {
+                        owners[i] = Some(Owner::Tie);
+                    },
+                    Some(_) => {},
+                }
+            }
+        }
+        frontier = next;
+    }
+    owners.into_iter().map(Option::unwrap).collect()
+}
+
+/// Returns the coordinates sorted and without duplicates.
+fn unique_sites(coords: &[Point]) -> Vec<Point> {
+    let mut sites = coords.to_owned();
+    sites.sort();
+    sites.dedup();
+    sites
+}
+
+/// Like `find_largest_area`, but labels all cells in one sweep instead
+/// of comparing each cell with each site.
+fn find_largest_area_indexed(coords: &[Point]) -> Option<(Point, usize)> {
+    let sites = unique_sites(coords);
+    let board = sites.iter().cloned().collect::<Option<Board>>()?;
+    let mut areas = vec![Some(0); sites.len()];
+    for (point, owner) in board.iter().zip(flood_owners(&sites, &board)) {
+        if let Owner::Site(i) = owner {
+            if point.is_on_edge(&board) {
+                areas[i] = None;
+            } else if let Some(area) = areas[i].as_mut() {
+                *area += 1;
+            }
+        }
+    }
+    sites
+        .into_iter()
+        .zip(areas)
+        .filter_map(|(p, area)| area.map(|area| (p, area)))
+        .max_by_key(|&(_, area)| area)
+}
+
+/// Returns the sites whose area is infinite, in sorted order.
+///
+/// These are exactly the sites that are nearest to some cell on the
+/// edge of the board: the cells beyond it are then nearer still.
+fn infinite_sites(coords: &[Point]) -> Vec<Point> {
+    let sites = unique_sites(coords);
+    let board = match sites.iter().cloned().collect::<Option<Board>>() {
+        Some(board) => board,
+        None => return Vec::new(),
+    };
+    let mut is_infinite = vec![false; sites.len()];
+    for (point, owner) in board.iter().zip(flood_owners(&sites, &board)) {
+        match owner {
+            Owner::Site(i) if point.is_on_edge(&board) => is_infinite[i] = true,
+            _ => {},
+        }
+    }
+    sites
+        .into_iter()
+        .zip(is_infinite)
+        .filter(|&(_, is_infinite)| is_infinite)
+        .map(|(site, _)| site)
+        .collect()
+}
+
+/// Draws the nearest site of each cell on the board.
+///
+/// Sites are labelled `a`, `b`, ... in the order of `coords`, wrapping
+/// around after `z`. A site's own cell is in uppercase; ties are `.`.
+fn render(coords: &[Point]) -> String {
+    let sites = unique_sites(coords);
+    let board = match sites.iter().cloned().collect::<Option<Board>>() {
+        Some(board) => board,
+        None => return String::new(),
+    };
+    let labels = sites
+        .iter()
+        .map(|site| {
+            let index = coords.iter().position(|c| c == site).unwrap();
+            (b'a' + (index % 26) as u8) as char
+        })
+        .collect::<Vec<_>>();
+    let owners = flood_owners(&sites, &board);
+    let mut output = String::with_capacity((board.width() + 1) * board.height());
+    for y in board.top_left.y..=board.bottom_right.y {
+        for x in board.top_left.x..=board.bottom_right.x {
+            let point = Point { x, y };
+            output.push(match owners[board.cell_index(point)] {
+                Owner::Site(i) if sites[i] == point => labels[i].to_ascii_uppercase(),
+                Owner::Site(i) => labels[i],
+                Owner::Tie => '.',
+            });
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// Counts the points whose total distance to all coordinates is less
+/// than `max_distance`.
+///
+/// A point that lies `k` steps outside the coordinates' bounding box,
+/// e.g. to the left, is at least `k` steps away from each of the `n`
+/// coordinates. Its total distance is at least `n * k`, so it can only
+/// be in the region if `k < max_distance / n`. Padding the board by
+/// `max_distance / n` in each direction thus covers the whole region.
 fn find_area_within_total_distance(coords: &[Point], max_distance: i32) -> usize {
-    let mut board = coords.iter().cloned().collect::<Option<Board>>().unwrap();
-    board.extend_to(board.top_left - (max_distance, max_distance));
-    board.extend_to(board.bottom_right + (max_distance, max_distance));
+    let mut board = match coords.iter().cloned().collect::<Option<Board>>() {
+        Some(board) => board,
+        None => return 0,
+    };
+    let padding = max_distance / coords.len() as i32;
+    board.extend_to(board.top_left - (padding, padding));
+    board.extend_to(board.bottom_right + (padding, padding));
     board
         .iter()
         .filter(|&p| coords.iter().map(|&c| p.distance(c)).sum::<i32>() < max_distance)
         .count()
 }
 
+/// The default threshold for the total distance to all coordinates.
+const DEFAULT_MAX_DISTANCE: i32 = 10000;
+
+/// Parses the threshold for the total distance, which must be positive.
+fn parse_max_distance(arg: Option<&str>) -> Result<i32, String> {
+    let arg = match arg {
+        Some(arg) => arg,
+        None => return Ok(DEFAULT_MAX_DISTANCE),
+    };
+    match arg.parse() {
+        Ok(max_distance) if max_distance > 0 => Ok(max_distance),
+        _ => Err(format!("not a positive distance: {:?}", arg)),
+    }
+}
+
 fn main() {
+    let (flags, args): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let max_distance = parse_max_distance(args.first().map(String::as_str)).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    });
     let stdin = io::stdin();
     let coords = stdin
         .lock()
@@ -203,8 +428,167 @@ fn main() {
         .map(|line| line.unwrap().trim().parse())
         .collect::<Result<Vec<Point>, _>>()
         .unwrap();
-    let (maxp, max) = find_largest_area(coords.iter().cloned()).unwrap();
-    println!("best point {} (area = {})", maxp, max);
-    let min_area = find_area_within_total_distance(&coords, 10000);
+    if coords.is_empty() {
+        println!("no coordinates provided");
+        return;
+    }
+    if flags.iter().any(|flag| flag == "--render") {
+        print!("{}", render(&coords));
+    }
+    let (maxp, max) = find_largest_area_indexed(&coords).unwrap();
+    println!("best point ({}) (area = {})", maxp, max);
+    println!("infinite areas: {}", infinite_sites(&coords).len());
+    let min_area = find_area_within_total_distance(&coords, max_distance);
     println!("minimal area: {}", min_area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_coords() -> Vec<Point> {
+        ["1, 1", "1, 6", "8, 3", "3, 4", "5, 5", "8, 9"]
+            .iter()
+            .map(|line| line.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_find_largest_area() {
+        let coords = example_coords();
+        let expected = Some((Point { x: 5, y: 5 }, 17));
+        assert_eq!(find_largest_area(coords.iter().cloned()), expected);
+        assert_eq!(find_largest_area_indexed(&coords), expected);
+    }
+
+    #[test]
+    fn test_flood_owners() {
+        let coords = example_coords();
+        let board = coords.iter().cloned().collect::<Option<Board>>().unwrap();
+        let owners = flood_owners(&coords, &board);
+        for (point, owner) in board.iter().zip(owners) {
+            let expected = find_nearest_point(point, coords.iter().cloned())
+                .map(|p| Owner::Site(coords.iter().position(|&c| c == p).unwrap()))
+                .unwrap_or(Owner::Tie);
+            assert_eq!(owner, expected, "{}", point);
+        }
+    }
+
+    #[test]
+    fn test_area_within_total_distance() {
+        let coords = example_coords();
+        assert_eq!(find_area_within_total_distance(&coords, 32), 16);
+        // With few coordinates, the region reaches far beyond their
+        // bounding box.
+        let single = [Point { x: 0, y: 0 }];
+        assert_eq!(find_area_within_total_distance(&single, 3), 13);
+        let pair = [Point { x: 0, y: 0 }, Point { x: 0, y: 0 }];
+        assert_eq!(find_area_within_total_distance(&pair, 7), 25);
+    }
+
+    #[test]
+    fn test_tie_break() {
+        let sites = [Point { x: 0, y: 0 }, Point { x: 4, y: 0 }];
+        let middle = Point { x: 2, y: 3 };
+        let nearest =
+            |refp, tie_break| find_nearest_point_with(refp, sites.iter().cloned(), tie_break);
+        assert_eq!(nearest(middle, TieBreak::NoOwner), None);
+        assert_eq!(nearest(middle, TieBreak::Lowest), Some(sites[0]));
+        assert_eq!(find_nearest_point(middle, sites.iter().cloned()), None);
+        let off_middle = Point { x: 3, y: 0 };
+        assert_eq!(nearest(off_middle, TieBreak::NoOwner), Some(sites[1]));
+        assert_eq!(nearest(off_middle, TieBreak::Lowest), Some(sites[1]));
+    }
+
+    #[test]
+    fn test_parse_max_distance() {
+        assert_eq!(parse_max_distance(None), Ok(10000));
+        assert_eq!(parse_max_distance(Some("32")), Ok(32));
+        assert!(parse_max_distance(Some("0")).is_err());
+        assert!(parse_max_distance(Some("-5")).is_err());
+        assert!(parse_max_distance(Some("ten")).is_err());
+        let coords = example_coords();
+        let max_distance = parse_max_distance(Some("32")).unwrap();
+        assert_eq!(find_area_within_total_distance(&coords, max_distance), 16);
+    }
+
+    #[test]
+    fn test_infinite_sites() {
+        let expected = ["1, 1", "1, 6", "8, 3", "8, 9"]
+            .iter()
+            .map(|line| line.parse().unwrap())
+            .collect::<Vec<Point>>();
+        assert_eq!(infinite_sites(&example_coords()), expected);
+        assert_eq!(infinite_sites(&[]), []);
+    }
+
+    #[test]
+    fn test_render() {
+        // The diagram from the puzzle, plus one more row of padding.
+        let expected = "\
+aaaaa.cccc
+aAaaa.cccc
+aaaddecccc
+aadddeccCc
+..dDdeeccc
+bb.deEeecc
+bBb.eeee..
+bbb.eeefff
+bbb.eeffff
+bbb.ffffFf
+bbb.ffffff
+";
+        assert_eq!(render(&example_coords()), expected);
+        assert_eq!(render(&[]), "");
+    }
+
+    #[test]
+    fn test_distance() {
+        let origin = Point { x: 0, y: 0 };
+        let p = Point { x: 3, y: -4 };
+        assert_eq!(origin.distance(origin), 0);
+        assert_eq!(origin.distance(p), 7);
+        assert_eq!(p.distance(origin), 7);
+        assert_eq!(p.distance(p + (-1, 2)), 3);
+    }
+
+    #[test]
+    fn test_points_within() {
+        let center = Point { x: 2, y: -1 };
+        let mut points = points_within(center, 1).collect::<Vec<_>>();
+        points.sort();
+        let mut expected = vec![
+            center,
+            center + (1, 0),
+            center - (1, 0),
+            center + (0, 1),
+            center - (0, 1),
+        ];
+        expected.sort();
+        assert_eq!(points, expected);
+        assert_eq!(points_within(center, 0).collect::<Vec<_>>(), [center]);
+        assert_eq!(points_within(center, -1).count(), 0);
+        let points = points_within(center, 3).collect::<Vec<_>>();
+        assert_eq!(points.len(), 25);
+        assert!(points.iter().all(|p| p.distance(center) <= 3));
+    }
+
+    #[test]
+    fn test_empty_input() {
+        assert_eq!(find_largest_area(std::iter::empty()), None);
+        assert_eq!(find_largest_area_indexed(&[]), None);
+        assert_eq!(find_area_within_total_distance(&[], 32), 0);
+    }
+
+    #[test]
+    fn test_point_round_trip() {
+        assert_eq!("3, 4".parse::<Point>().unwrap().to_string(), "3, 4");
+        assert_eq!("-3, 0".parse::<Point>().unwrap().to_string(), "-3, 0");
+        for s in ["3,4", " 3 , 4 ", "\t3,\t4\n"].iter() {
+            assert_eq!(s.parse::<Point>().unwrap(), Point { x: 3, y: 4 });
+        }
+        for s in ["", "3", "3, ", "3; 4", "3, 4, 5"].iter() {
+            assert!(s.parse::<Point>().is_err(), "{:?}", s);
+        }
+    }
+}
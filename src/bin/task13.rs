@@ -1,7 +1,10 @@
 use std::{
     collections::BTreeMap,
+    env,
+    fmt::{self, Display},
     io::{self, BufRead},
     iter::{FromIterator, FusedIterator},
+    process,
     str::FromStr,
 };
 
@@ -9,8 +12,10 @@ use std::{
 struct Task(u8);
 
 impl Task {
+    /// Returns the position of the task's letter in the alphabet,
+    /// regardless of case.
     fn get_duration(&self) -> u32 {
-        (self.0 - b'A' + 1) as u32
+        (self.0.to_ascii_uppercase() - b'A' + 1) as u32
     }
 }
 
@@ -28,23 +33,40 @@ struct Prerequisite {
 impl FromStr for Prerequisite {
     type Err = BadLine;
 
+    /// Parses a line of the form "Step X must be finished before step Y
+    /// can begin."
+    ///
+    /// The amount of whitespace between words doesn't matter. Tasks are
+    /// single letters of either case.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use boolinator::Boolinator;
-
-        let &needed = s.as_bytes().get(5).ok_or(BadLine)?;
-        let &task = s.as_bytes().get(36).ok_or(BadLine)?;
-        (b'A'..=b'Z').any(|c| c == needed).ok_or(BadLine)?;
-        (b'A'..=b'Z').any(|c| c == task).ok_or(BadLine)?;
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        let (needed, task) = match words[..] {
+            ["Step", needed, "must", "be", "finished", "before", "step", task, "can", "begin."] => {
+                (needed, task)
+            },
+            _ => return Err(BadLine),
+        };
         Ok(Prerequisite {
-            task: Task(task),
-            needed: Task(needed),
+            task: task.parse()?,
+            needed: needed.parse()?,
         })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 struct BadLine;
 
+impl FromStr for Task {
+    type Err = BadLine;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            &[c] if c.is_ascii_alphabetic() => Ok(Task(c)),
+            _ => Err(BadLine),
+        }
+    }
+}
+
 #[derive(Clone, Default, Debug)]
 struct PrerequisiteTable(BTreeMap<Task, Vec<Task>>);
 
@@ -57,6 +79,16 @@ impl PrerequisiteTable {
         self.0.is_empty()
     }
 
+    /// Returns the unmet prerequisites of `task`, or `None` if it is
+    /// unknown or already handed out.
+    fn prerequisites_of(&self, task: &Task) -> Option<&[Task]> {
+        self.0.get(task).map(Vec::as_slice)
+    }
+
+    fn remaining_tasks(&self) -> Vec<Task> {
+        self.0.keys().cloned().collect()
+    }
+
     fn next_doable_task(&mut self) -> Option<Task> {
         use boolinator::Boolinator;
 
@@ -138,11 +170,11 @@ impl Worker {
         self.task.is_none()
     }
 
-    fn give_task(&mut self, task: Task, extra_time: u32) -> Result<(), IsBusy> {
+    fn give_task(&mut self, task: Task, duration: u32) -> Result<(), IsBusy> {
         match self.task {
             Some(_) => Err(IsBusy),
             None => {
-                self.time_until_ready = task.get_duration() + extra_time;
+                self.time_until_ready = duration;
                 self.task = Some(task);
                 Ok(())
             },
@@ -161,33 +193,154 @@ impl Worker {
 #[derive(Debug)]
 struct IsBusy;
 
+/// The remaining tasks can never be done because they depend on each
+/// other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Deadlock {
+    stuck: Vec<Task>,
+}
+
+impl Display for Deadlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stuck = tasks_to_string(self.stuck.iter().cloned());
+        write!(f, "cyclic prerequisites among tasks {}", stuck)
+    }
+}
+
+/// Orders the tasks so that each comes after its prerequisites.
+///
+/// Of all tasks that are ready, the alphabetically first one goes
+/// first.
+fn topological_order(prerequisites: PrerequisiteTable) -> Vec<Task> {
+    prerequisites.into_iter().collect()
+}
+
+/// Finds the chain of dependent tasks with the longest total duration.
+///
+/// No schedule can finish faster than this chain, no matter how many
+/// workers there are. The chain is ordered from first to last task.
+fn critical_path(prerequisites: &PrerequisiteTable) -> Vec<Task> {
+    // For each task, the longest duration of any chain ending in it,
+    // and the previous task in that chain.
+    let mut longest = BTreeMap::<Task, (u32, Option<Task>)>::new();
+    for task in topological_order(prerequisites.clone()) {
+        let previous = prerequisites
+            .prerequisites_of(&task)
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(|needed| longest.get(needed).map(|&(total, _)| (total, needed)))
+            .max_by_key(|&(total, _)| total);
+        let (total, previous) = match previous {
+            Some((total, needed)) => (total, Some(needed.clone())),
+            None => (0, None),
+        };
+        longest.insert(task.clone(), (total + task.get_duration(), previous));
+    }
+    let mut current = longest
+        .iter()
+        .max_by_key(|(_, &(total, _))| total)
+        .map(|(task, _)| task.clone());
+    let mut path = Vec::new();
+    while let Some(task) = current {
+        current = longest[&task].1.clone();
+        path.push(task);
+    }
+    path.reverse();
+    path
+}
+
 fn simulate_single_worker(prerequisites: PrerequisiteTable) -> String {
-    tasks_to_string(prerequisites.into_iter())
+    tasks_to_string(topological_order(prerequisites))
 }
 
+/// Simulates several workers doing the tasks in parallel.
+///
+/// Returns the order in which the tasks were finished, the total time,
+/// and the idle time. The idle time is the number of free workers,
+/// summed over all seconds in which some tasks were not handed out yet.
 fn simulate_parallel_workers(
+    prerequisites: PrerequisiteTable,
+    workers: usize,
+    extra_time_per_task: u32,
+) -> Result<(String, u32, u32), Deadlock> {
+    simulate_parallel_workers_with(
+        prerequisites,
+        workers,
+        Task::get_duration,
+        extra_time_per_task,
+    )
+}
+
+/// Like `simulate_parallel_workers`, but each task takes
+/// `duration(task)` seconds plus the extra time.
+///
+/// Every task takes at least one second, even if its total time is zero.
+fn simulate_parallel_workers_with(
+    prerequisites: PrerequisiteTable,
+    workers: usize,
+    duration: impl Fn(&Task) -> u32,
+    extra_time_per_task: u32,
+) -> Result<(String, u32, u32), Deadlock> {
+    let simulation =
+        simulate_parallel_workers_timeline(prerequisites, workers, duration, extra_time_per_task)?;
+    Ok((simulation.order, simulation.time, simulation.idle_time))
+}
+
+/// What each worker is doing, indexed first by second, then by worker.
+type Timeline = Vec<Vec<Option<Task>>>;
+
+/// The full outcome of `simulate_parallel_workers_timeline`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Simulation {
+    order: String,
+    time: u32,
+    idle_time: u32,
+    timeline: Timeline,
+}
+
+/// Like `simulate_parallel_workers_with`, but also records the timeline.
+fn simulate_parallel_workers_timeline(
     mut prerequisites: PrerequisiteTable,
     workers: usize,
+    duration: impl Fn(&Task) -> u32,
     extra_time_per_task: u32,
-) -> (String, u32) {
+) -> Result<Simulation, Deadlock> {
     let mut workers = vec![Worker::default(); workers];
     let mut finished = Vec::new();
+    let mut timeline = Timeline::new();
+    let mut idle_time = 0;
     for time in 0.. {
         if !prerequisites.is_empty() {
             // There are remaining tasks, hand them out.
             for worker in &mut workers {
                 if worker.is_free() {
                     if let Some(task) = prerequisites.next_doable_task() {
-                        worker.give_task(task, extra_time_per_task).unwrap()
+                        let time = duration(&task) + extra_time_per_task;
+                        worker.give_task(task, time).unwrap()
                     }
                 }
             }
+            // If nobody can do anything, nothing will ever change.
+            if workers.iter().all(|w| w.is_free()) {
+                let stuck = prerequisites.remaining_tasks();
+                return Err(Deadlock { stuck });
+            }
+            if !prerequisites.is_empty() {
+                idle_time += workers.iter().filter(|w| w.is_free()).count() as u32;
+            }
         } else {
             // There are no remaining tasks, wait for workers to finish.
             if workers.iter().all(|w| w.is_free()) {
-                return (tasks_to_string(finished), time);
+                let simulation = Simulation {
+                    order: tasks_to_string(finished),
+                    time,
+                    idle_time,
+                    timeline,
+                };
+                return Ok(simulation);
             }
         }
+        timeline.push(workers.iter().map(|w| w.task.clone()).collect());
         // Have them perform one unit of work.
         for worker in &mut workers {
             if let Some(task) = worker.step() {
@@ -199,7 +352,33 @@ fn simulate_parallel_workers(
     panic!("overflow");
 }
 
+/// Parses an optional argument, falling back to `default` if it's
+/// missing.
+fn parse_arg<T: FromStr>(arg: Option<&String>, name: &str, default: T) -> Result<T, String> {
+    match arg {
+        Some(arg) => arg
+            .parse()
+            .map_err(|_| format!("bad number of {}: {:?}", name, arg)),
+        None => Ok(default),
+    }
+}
+
+/// Parses the number of workers and the extra time per task.
+fn parse_args(args: &[String]) -> Result<(usize, u32), String> {
+    let workers = parse_arg(args.first(), "workers", 5)?;
+    if workers == 0 {
+        return Err("need at least one worker".to_owned());
+    }
+    let extra_time = parse_arg(args.get(1), "extra seconds", 60)?;
+    Ok((workers, extra_time))
+}
+
 fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let (workers, extra_time) = parse_args(&args).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    });
     let stdin = io::stdin();
     let tasks = stdin
         .lock()
@@ -210,9 +389,21 @@ fn main() {
         "single worker order:   {}",
         simulate_single_worker(tasks.clone())
     );
-    let (tasks, time) = simulate_parallel_workers(tasks, 5, 60);
+    let path = critical_path(&tasks);
+    let path_time = path.iter().map(Task::get_duration).sum::<u32>();
+    println!(
+        "critical path: {} ({} seconds)",
+        tasks_to_string(path),
+        path_time
+    );
+    let (tasks, time, idle_time) = simulate_parallel_workers(tasks, workers, extra_time)
+        .unwrap_or_else(|err| {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        });
     println!("parallel worker order: {}", tasks);
     println!("parallel worker time: {}", time);
+    println!("parallel worker idle time: {}", idle_time);
 }
 
 #[cfg(test)]
@@ -264,8 +455,119 @@ mod tests {
     #[test]
     fn test_parallel() {
         let prerequisites = example_data();
-        let (tasks, time) = simulate_parallel_workers(prerequisites, 2, 0);
+        let (tasks, time, idle_time) = simulate_parallel_workers(prerequisites, 2, 0).unwrap();
         assert_eq!(tasks, "CABFDE");
         assert_eq!(time, 15);
+        // The second worker waits during seconds 0-2 for C and during
+        // second 9 for D.
+        assert_eq!(idle_time, 4);
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = |args: &[&str]| args.iter().map(|&arg| arg.to_owned()).collect::<Vec<_>>();
+        assert_eq!(parse_args(&args(&[])), Ok((5, 60)));
+        assert_eq!(parse_args(&args(&["2"])), Ok((2, 60)));
+        assert_eq!(parse_args(&args(&["2", "0"])), Ok((2, 0)));
+        assert!(parse_args(&args(&["-2", "0"])).is_err());
+        assert!(parse_args(&args(&["0", "0"])).is_err());
+        assert!(parse_args(&args(&["2", "x"])).is_err());
+    }
+
+    #[test]
+    fn test_deadlock() {
+        let prerequisites = [
+            "Step A must be finished before step B can begin.",
+            "Step B must be finished before step A can begin.",
+            "Step C must be finished before step A can begin.",
+        ]
+        .iter()
+        .map(|line| line.parse().unwrap())
+        .collect::<PrerequisiteTable>();
+        let result = simulate_parallel_workers(prerequisites, 2, 0);
+        let stuck = vec![Task(b'A'), Task(b'B')];
+        assert_eq!(result, Err(Deadlock { stuck }));
+    }
+
+    #[test]
+    fn test_timeline() {
+        let Simulation { time, timeline, .. } =
+            simulate_parallel_workers_timeline(example_data(), 2, Task::get_duration, 0).unwrap();
+        assert_eq!(timeline.len(), time as usize);
+        assert_eq!(timeline[0], [Some(Task(b'C')), None]);
+        assert_eq!(timeline[3], [Some(Task(b'A')), Some(Task(b'F'))]);
+        assert_eq!(timeline[4], [Some(Task(b'B')), Some(Task(b'F'))]);
+        assert_eq!(timeline[14], [Some(Task(b'E')), None]);
+    }
+
+    #[test]
+    fn test_prerequisites_of() {
+        let mut prerequisites = example_data();
+        let mut needed = prerequisites
+            .prerequisites_of(&Task(b'E'))
+            .unwrap()
+            .to_vec();
+        needed.sort();
+        assert_eq!(needed, [Task(b'B'), Task(b'D'), Task(b'F')]);
+        assert_eq!(prerequisites.prerequisites_of(&Task(b'C')), Some(&[][..]));
+        assert_eq!(prerequisites.prerequisites_of(&Task(b'X')), None);
+        let first = prerequisites.next_doable_task().unwrap();
+        prerequisites.finish_task(&first);
+        assert_eq!(prerequisites.prerequisites_of(&first), None);
+        assert_eq!(prerequisites.prerequisites_of(&Task(b'A')), Some(&[][..]));
+    }
+
+    #[test]
+    fn test_custom_duration() {
+        let result = simulate_parallel_workers_with(example_data(), 2, |_| 3, 0);
+        assert_eq!(result, Ok(("CAFBDE".to_owned(), 12, 3)));
+        let result = simulate_parallel_workers_with(example_data(), 2, |_| 3, 1);
+        assert_eq!(result, Ok(("CAFBDE".to_owned(), 16, 4)));
+        let result = simulate_parallel_workers_with(example_data(), 2, Task::get_duration, 0);
+        assert_eq!(result, simulate_parallel_workers(example_data(), 2, 0));
+    }
+
+    #[test]
+    fn test_topological_order() {
+        let expected = b"CABDFE".iter().map(|&t| Task(t)).collect::<Vec<_>>();
+        assert_eq!(topological_order(example_data()), expected);
+    }
+
+    #[test]
+    fn test_parse_prerequisite() {
+        let parse = |line: &str| line.parse::<Prerequisite>();
+        let expected = Prerequisite {
+            task: Task(b'A'),
+            needed: Task(b'C'),
+        };
+        assert_eq!(
+            parse("Step C must be finished before step A can begin."),
+            Ok(expected.clone())
+        );
+        assert_eq!(
+            parse("  Step C  must be finished\tbefore step  A can begin.\n"),
+            Ok(expected)
+        );
+        let lowercase = parse("Step c must be finished before step a can begin.").unwrap();
+        assert_eq!(lowercase.task.get_duration(), 1);
+        assert_eq!(lowercase.needed.get_duration(), 3);
+        let malformed = [
+            "",
+            "Step C must be finished before step A can begin",
+            "Step C must be finished after step A can begin.",
+            "Step CD must be finished before step A can begin.",
+            "Step 1 must be finished before step A can begin.",
+        ];
+        for line in malformed.iter() {
+            assert_eq!(parse(line), Err(BadLine), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_critical_path() {
+        let path = critical_path(&example_data());
+        assert_eq!(path.last(), Some(&Task(b'E')));
+        assert_eq!(path, [Task(b'C'), Task(b'F'), Task(b'E')]);
+        assert_eq!(critical_path(&PrerequisiteTable::default()), []);
     }
 }
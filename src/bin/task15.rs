@@ -1,55 +1,310 @@
 use std::{
+    env, fmt,
     io::{self, Read},
-    iter,
+    iter, mem,
+    num::ParseIntError,
+    process,
 };
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 struct Node {
     children: Vec<Node>,
     metadata: Vec<u32>,
 }
 
+impl Drop for Node {
+    /// Drops the descendants one by one, so that deep trees don't
+    /// overflow the stack.
+    fn drop(&mut self) {
+        let mut descendants = mem::take(&mut self.children);
+        while let Some(mut node) = descendants.pop() {
+            descendants.append(&mut node.children);
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    /// Writes one line per node with its metadata, indenting each node
+    /// by two spaces more than its parent.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut pending = vec![(0, self)];
+        while let Some((depth, node)) = pending.pop() {
+            writeln!(f, "{:indent$}{:?}", "", node.metadata, indent = 2 * depth)?;
+            pending.extend(node.children.iter().rev().map(|child| (depth + 1, child)));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 struct NoElement;
 
+#[allow(dead_code)]
 fn build_tree(numbers: &mut impl Iterator<Item = u32>) -> Result<Node, NoElement> {
     let num_children = numbers.next().ok_or(NoElement)? as usize;
     let num_metadata = numbers.next().ok_or(NoElement)? as usize;
     let children = iter::repeat_with(|| build_tree(numbers))
         .take(num_children)
         .collect::<Result<_, _>>()?;
-    let metadata = numbers.take(num_metadata).collect();
+    let metadata = read_metadata(numbers, num_metadata)?;
     Ok(Node { children, metadata })
 }
 
-fn visit<V: FnMut(&[u32])>(root: &Node, visitor: &mut V) {
+/// Reads exactly `num_metadata` entries.
+fn read_metadata(
+    numbers: &mut impl Iterator<Item = u32>,
+    num_metadata: usize,
+) -> Result<Vec<u32>, NoElement> {
+    (0..num_metadata)
+        .map(|_| numbers.next().ok_or(NoElement))
+        .collect()
+}
+
+/// A node whose children are still being built by `build_tree_iterative`.
+struct PartialNode {
+    missing_children: usize,
+    num_metadata: usize,
+    children: Vec<Node>,
+}
+
+impl PartialNode {
+    fn from_header(numbers: &mut impl Iterator<Item = u32>) -> Result<Self, NoElement> {
+        let missing_children = numbers.next().ok_or(NoElement)? as usize;
+        let num_metadata = numbers.next().ok_or(NoElement)? as usize;
+        Ok(PartialNode {
+            missing_children,
+            num_metadata,
+            children: Vec::new(),
+        })
+    }
+}
+
+/// Like `build_tree`, but keeps the unfinished nodes on an explicit
+/// stack instead of the call stack.
+fn build_tree_iterative(numbers: &mut impl Iterator<Item = u32>) -> Result<Node, NoElement> {
+    let mut stack = vec![PartialNode::from_header(numbers)?];
+    loop {
+        let top = stack.last_mut().unwrap();
+        if top.missing_children > 0 {
+            top.missing_children -= 1;
+            let child = PartialNode::from_header(numbers)?;
+            stack.push(child);
+            continue;
+        }
+        let top = stack.pop().unwrap();
+        let node = Node {
+            children: top.children,
+            metadata: read_metadata(numbers, top.num_metadata)?,
+        };
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => return Ok(node),
+        }
+    }
+}
+
+/// Error that occurs while parsing a whole tree from a string.
+#[derive(Debug, PartialEq, Eq)]
+enum ParseTreeError {
+    BadNumber {
+        position: usize,
+        token: String,
+        error: ParseIntError,
+    },
+    Truncated,
+    TrailingNumbers(usize),
+}
+
+impl fmt::Display for ParseTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseTreeError::BadNumber {
+                position,
+                token,
+                error,
+            } => write!(f, "number {}: bad number {:?}: {}", position, token, error),
+            ParseTreeError::Truncated => write!(f, "tree is truncated, input ended too early"),
+            ParseTreeError::TrailingNumbers(n) => {
+                write!(f, "numbers left after the end of the tree: {}", n)
+            },
+        }
+    }
+}
+
+impl From<NoElement> for ParseTreeError {
+    fn from(_: NoElement) -> Self {
+        ParseTreeError::Truncated
+    }
+}
+
+/// Parses a whitespace-separated list of numbers into a tree.
+///
+/// Unlike `build_tree`, this requires that the input contains exactly
+/// one tree, with no numbers left over.
+fn parse_tree(s: &str) -> Result<Node, ParseTreeError> {
+    let numbers = s
+        .split_whitespace()
+        .enumerate()
+        .map(|(i, token)| {
+            token.parse().map_err(|error| ParseTreeError::BadNumber {
+                position: i + 1,
+                token: token.to_owned(),
+                error,
+            })
+        })
+        .collect::<Result<Vec<u32>, _>>()?;
+    let mut numbers = numbers.into_iter();
+    let tree = build_tree_iterative(&mut numbers)?;
+    match numbers.len() {
+        0 => Ok(tree),
+        n => Err(ParseTreeError::TrailingNumbers(n)),
+    }
+}
+
+#[allow(dead_code)]
+fn visit_recursive<V: FnMut(&[u32])>(root: &Node, visitor: &mut V) {
     visitor(&root.metadata);
     for child in &root.children {
-        visit(child, visitor);
+        visit_recursive(child, visitor);
+    }
+}
+
+/// Visits the nodes in the same order as `visit_recursive`, but keeps
+/// the pending nodes on an explicit stack.
+fn visit<V: FnMut(&[u32])>(root: &Node, visitor: &mut V) {
+    let mut pending = vec![root];
+    while let Some(node) = pending.pop() {
+        visitor(&node.metadata);
+        pending.extend(node.children.iter().rev());
+    }
+}
+
+/// Like `visit`, but also passes each node's depth to the visitor. The
+/// root has depth 0.
+fn visit_with_depth<V: FnMut(usize, &Node)>(root: &Node, visitor: &mut V) {
+    let mut pending = vec![(0, root)];
+    while let Some((depth, node)) = pending.pop() {
+        visitor(depth, node);
+        pending.extend(node.children.iter().rev().map(|child| (depth + 1, child)));
     }
 }
 
 fn sum_metadata(root: &Node) -> u32 {
     let mut sum: u32 = 0;
-    let mut visitor = |data: &[u32]| {
-        sum += data.iter().sum::<u32>();
+    let mut visitor = |_, node: &Node| {
+        sum += node.metadata.iter().sum::<u32>();
     };
-    visit(root, &mut visitor);
+    visit_with_depth(root, &mut visitor);
     sum
 }
 
-fn get_node_value(node: &Node) -> u32 {
+fn node_count(root: &Node) -> usize {
+    let mut count = 0;
+    visit(root, &mut |_: &[u32]| count += 1);
+    count
+}
+
+/// Returns the number of nodes on the longest path from the root to a
+/// leaf, so a lone leaf has depth 1.
+fn max_depth(root: &Node) -> usize {
+    let mut max_depth = 0;
+    visit_with_depth(root, &mut |depth, _| max_depth = max_depth.max(depth + 1));
+    max_depth
+}
+
+/// Returns the child referenced by a metadata entry, if any.
+fn referenced_child(node: &Node, index: u32) -> Option<&Node> {
+    (index as usize)
+        .checked_sub(1)
+        .and_then(|i| node.children.get(i))
+}
+
+#[allow(dead_code)]
+fn get_node_value_recursive(node: &Node) -> u32 {
     if node.children.is_empty() {
         node.metadata.iter().sum()
     } else {
         node.metadata
             .iter()
-            .filter_map(|&i| node.children.get(i as usize - 1))
-            .map(|node| get_node_value(node))
+            .filter_map(|&i| referenced_child(node, i))
+            .map(get_node_value_recursive)
+            .sum()
+    }
+}
+
+/// A node whose value is being computed by `get_node_value_with`.
+struct PartialValue<'a> {
+    node: &'a Node,
+    /// The value of each child, once it has been computed.
+    child_values: Vec<Option<u32>>,
+    /// The number of metadata entries that have been looked at.
+    next_entry: usize,
+}
+
+impl<'a> PartialValue<'a> {
+    fn new(node: &'a Node) -> Self {
+        PartialValue {
+            node,
+            child_values: vec![None; node.children.len()],
+            next_entry: 0,
+        }
+    }
+
+    /// Returns the index of the next referenced child whose value is
+    /// still unknown.
+    fn next_missing_child(&mut self) -> Option<usize> {
+        while let Some(&entry) = self.node.metadata.get(self.next_entry) {
+            self.next_entry += 1;
+            let index = (entry as usize).checked_sub(1);
+            if let Some(None) = index.and_then(|i| self.child_values.get(i)) {
+                return index;
+            }
+        }
+        None
+    }
+
+    /// Returns the value once all referenced children are known.
+    fn value(&self) -> u32 {
+        if self.node.children.is_empty() {
+            return self.node.metadata.iter().sum();
+        }
+        self.node
+            .metadata
+            .iter()
+            .filter_map(|&i| (i as usize).checked_sub(1))
+            .filter_map(|i| self.child_values.get(i).cloned().flatten())
             .sum()
     }
 }
 
+fn get_node_value(root: &Node) -> u32 {
+    get_node_value_with(root, &mut |_| ())
+}
+
+/// Like `get_node_value_recursive`, but follows the references with an
+/// explicit stack. Each child's value is computed at most once, no
+/// matter how often its parent references it. `on_evaluate` is called
+/// whenever the value of a node has been computed.
+fn get_node_value_with<F: FnMut(&Node)>(root: &Node, on_evaluate: &mut F) -> u32 {
+    // Each entry also holds the node's index among its parent's children.
+    let mut stack = vec![(0, PartialValue::new(root))];
+    loop {
+        let (_, top) = stack.last_mut().unwrap();
+        if let Some(i) = top.next_missing_child() {
+            let child = PartialValue::new(&top.node.children[i]);
+            stack.push((i, child));
+            continue;
+        }
+        let (index, top) = stack.pop().unwrap();
+        on_evaluate(top.node);
+        let value = top.value();
+        match stack.last_mut() {
+            Some((_, parent)) => parent.child_values[index] = Some(value),
+            None => return value,
+        }
+    }
+}
+
 fn main() {
     let buffer: String = {
         let mut buffer = String::new();
@@ -57,7 +312,145 @@ fn main() {
         stdin.lock().read_to_string(&mut buffer).unwrap();
         buffer
     };
-    let tree = build_tree(&mut buffer.split_whitespace().map(|n| n.parse().unwrap())).unwrap();
+    let tree = parse_tree(&buffer).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    });
+    if env::args().skip(1).any(|arg| arg == "--tree") {
+        print!("{}", tree);
+    }
     println!("sum of metadata: {}", sum_metadata(&tree));
     println!("value of root node: {}", get_node_value(&tree));
+    println!("number of nodes: {}", node_count(&tree));
+    println!("maximum depth: {}", max_depth(&tree));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: [u32; 16] = [2, 3, 0, 3, 10, 11, 12, 1, 1, 0, 1, 99, 2, 1, 1, 2];
+
+    #[test]
+    fn test_build_tree() {
+        let recursive = build_tree(&mut EXAMPLE.iter().cloned()).unwrap();
+        let iterative = build_tree_iterative(&mut EXAMPLE.iter().cloned()).unwrap();
+        assert_eq!(iterative, recursive);
+        assert_eq!(iterative.metadata, [1, 1, 2]);
+        assert_eq!(iterative.children[1].children[0].metadata, [99]);
+        assert!(build_tree_iterative(&mut EXAMPLE[..5].iter().cloned()).is_err());
+    }
+
+    #[test]
+    fn test_parse_tree() {
+        let tree = parse_tree("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2\n").unwrap();
+        assert_eq!(tree, build_tree(&mut EXAMPLE.iter().cloned()).unwrap());
+        assert_eq!(parse_tree("1 1 0 1 5"), Err(ParseTreeError::Truncated));
+        assert_eq!(parse_tree("0 1"), Err(ParseTreeError::Truncated));
+        assert_eq!(parse_tree(""), Err(ParseTreeError::Truncated));
+        assert_eq!(parse_tree("4000000000 1"), Err(ParseTreeError::Truncated));
+        assert_eq!(
+            parse_tree("0 1 5 6 7"),
+            Err(ParseTreeError::TrailingNumbers(2))
+        );
+        assert!(matches!(
+            parse_tree("0 1 x"),
+            Err(ParseTreeError::BadNumber { position: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse_tree("1 1 0 1 five 3").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "number 5: bad number \"five\": invalid digit found in string"
+        );
+        let err = parse_tree("1 1 0 1").unwrap_err();
+        assert_eq!(err.to_string(), "tree is truncated, input ended too early");
+        let err = parse_tree("0 1 5 6").unwrap_err();
+        assert_eq!(err.to_string(), "numbers left after the end of the tree: 1");
+    }
+
+    #[test]
+    fn test_display() {
+        let tree = parse_tree("1 1 0 2 5 6 3").unwrap();
+        assert_eq!(tree.to_string(), "[3]\n  [5, 6]\n");
+        let tree = parse_tree("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+        let expected = "[1, 1, 2]\n  [10, 11, 12]\n  [2]\n    [99]\n";
+        assert_eq!(tree.to_string(), expected);
+    }
+
+    #[test]
+    fn test_value_memoization() {
+        let tree = parse_tree("2 4 0 1 5 0 1 7 2 2 2 1").unwrap();
+        let mut evaluations = 0;
+        let value = get_node_value_with(&tree, &mut |_| evaluations += 1);
+        assert_eq!(value, 3 * 7 + 5);
+        assert_eq!(value, get_node_value_recursive(&tree));
+        // The root and each child are evaluated once.
+        assert_eq!(evaluations, 3);
+        let tree = parse_tree("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+        let mut evaluations = 0;
+        assert_eq!(get_node_value_with(&tree, &mut |_| evaluations += 1), 66);
+        // The only grandchild is never referenced, so it is never evaluated.
+        assert_eq!(evaluations, 3);
+    }
+
+    #[test]
+    fn test_count_and_depth() {
+        let tree = parse_tree("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+        assert_eq!(node_count(&tree), 4);
+        assert_eq!(max_depth(&tree), 3);
+        let leaf = parse_tree("0 1 5").unwrap();
+        assert_eq!(node_count(&leaf), 1);
+        assert_eq!(max_depth(&leaf), 1);
+    }
+
+    #[test]
+    fn test_visit_with_depth() {
+        let tree = parse_tree("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+        let mut pairs = Vec::new();
+        visit_with_depth(&tree, &mut |depth, node| {
+            pairs.push((depth, node.metadata.len()))
+        });
+        assert_eq!(pairs, [(0, 3), (1, 3), (1, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn test_build_deep_tree() {
+        // Each node has one child and one metadata entry.
+        const DEPTH: usize = 100_000;
+        let headers = iter::repeat_n([1, 1].iter(), DEPTH - 1).flatten();
+        let leaf = [0, 1].iter();
+        let metadata = iter::repeat_n(&1, DEPTH);
+        let mut numbers = headers.chain(leaf).chain(metadata).cloned();
+        let tree = build_tree_iterative(&mut numbers).unwrap();
+        assert!(numbers.next().is_none());
+        let mut node = &tree;
+        let mut depth = 1;
+        while let Some(child) = node.children.first() {
+            assert_eq!(node.metadata, [1]);
+            node = child;
+            depth += 1;
+        }
+        assert_eq!(depth, DEPTH);
+        assert_eq!(sum_metadata(&tree), DEPTH as u32);
+        assert_eq!(get_node_value(&tree), 1);
+        assert_eq!(node_count(&tree), DEPTH);
+        assert_eq!(max_depth(&tree), DEPTH);
+    }
+
+    #[test]
+    fn test_sum_and_value() {
+        let tree = build_tree_iterative(&mut EXAMPLE.iter().cloned()).unwrap();
+        assert_eq!(sum_metadata(&tree), 138);
+        assert_eq!(get_node_value(&tree), 66);
+        assert_eq!(get_node_value_recursive(&tree), 66);
+        let mut iterative = Vec::new();
+        visit(&tree, &mut |data: &[u32]| iterative.push(data.to_vec()));
+        let mut recursive = Vec::new();
+        visit_recursive(&tree, &mut |data: &[u32]| recursive.push(data.to_vec()));
+        assert_eq!(iterative, recursive);
+    }
 }
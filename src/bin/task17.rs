@@ -1,5 +1,5 @@
-use intrusive_collections::{intrusive_adapter, LinkedList, LinkedListLink};
 use std::{
+    collections::VecDeque,
     fmt::{self, Display},
     io::{self, Read},
     ops::AddAssign,
@@ -12,7 +12,7 @@ struct GameInfo {
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
-struct Score(u32);
+struct Score(u64);
 
 impl Display for Score {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -22,7 +22,7 @@ impl Display for Score {
 
 impl AddAssign<Marble> for Score {
     fn add_assign(&mut self, other: Marble) {
-        self.0 += other.value();
+        self.0 += u64::from(other.value());
     }
 }
 
@@ -35,32 +35,6 @@ impl Marble {
     }
 }
 
-struct Node {
-    value: Marble,
-    link: LinkedListLink,
-}
-
-impl Node {
-    fn into_marble(self) -> Marble {
-        self.value
-    }
-
-    fn value(&self) -> u32 {
-        self.value.value()
-    }
-}
-
-impl From<Marble> for Box<Node> {
-    fn from(marble: Marble) -> Self {
-        Box::new(Node {
-            value: marble,
-            link: LinkedListLink::new(),
-        })
-    }
-}
-
-intrusive_adapter!(Adap = Box<Node>: Node { link: LinkedListLink });
-
 fn parse_game_info<R: Read>(mut r: R) -> io::Result<GameInfo> {
     let mut s = String::new();
     r.read_to_string(&mut s)?;
@@ -77,36 +51,33 @@ fn parse_game_info<R: Read>(mut r: R) -> io::Result<GameInfo> {
 }
 
 fn play_game(info: GameInfo) -> Score {
+    play_game_full(info).into_iter().max().unwrap()
+}
+
+/// Plays the game and returns every player's final score. The first
+/// player is the one who places marble 1.
+fn play_game_full(info: GameInfo) -> Vec<Score> {
     let mut players = vec![Score::default(); info.num_players as usize];
     let mut marbles = (0..=info.last_marble).map(Marble);
-    let mut circle = LinkedList::new(Adap::new());
-    circle.push_front(marbles.next().unwrap().into());
-    let mut cursor = circle.cursor_mut();
+    // The circle runs clockwise from front to back and wraps around.
+    // The current marble is always at the back.
+    let mut circle = VecDeque::with_capacity(info.last_marble as usize + 1);
+    circle.push_back(marbles.next().unwrap());
     for marble in marbles {
         let turn = marble.value();
         if turn % 23 == 0 {
             let player = (turn as usize - 1) % players.len();
             let score = &mut players[player];
             *score += marble;
-            for _ in 0..7 {
-                cursor.move_prev();
-                if cursor.is_null() {
-                    cursor.move_prev();
-                }
-            }
-            *score += cursor.remove().unwrap().into_marble();
+            circle.rotate_right(7);
+            *score += circle.pop_back().unwrap();
+            circle.rotate_left(1);
         } else {
-            cursor.move_next();
-            if cursor.is_null() {
-                cursor.move_next();
-            }
-            debug_assert!(!cursor.is_null());
-            cursor.insert_after(marble.into());
-            cursor.move_next();
-            debug_assert!(cursor.get().unwrap().value() == turn);
+            circle.rotate_left(1);
+            circle.push_back(marble);
         }
     }
-    players.into_iter().max().unwrap()
+    players
 }
 
 fn main() {
@@ -168,4 +139,27 @@ mod tests {
             Score(37305)
         );
     }
+
+    #[test]
+    fn full_scoreboard() {
+        let scores = play_game_full(GameInfo {
+            num_players: 9,
+            last_marble: 25,
+        });
+        assert_eq!(scores.len(), 9);
+        let winner = (0..scores.len()).max_by_key(|&i| &scores[i]).unwrap();
+        assert_eq!(winner, 4);
+        assert_eq!(scores[winner], Score(32));
+        assert_eq!(scores.iter().filter(|&score| score.0 > 0).count(), 1);
+    }
+
+    #[test]
+    fn large_game() {
+        let score = play_game(GameInfo {
+            num_players: 1,
+            last_marble: 500_000,
+        });
+        assert!(score.0 > u64::from(u32::MAX));
+        assert_eq!(score, Score(7_129_593_249));
+    }
 }
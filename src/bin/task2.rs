@@ -1,23 +1,337 @@
+use aoc_2018::parse_steps;
 use std::{
-    collections::HashSet,
-    io::{self, BufRead},
+    collections::{HashMap, HashSet},
+    io, iter,
+    ops::Range,
+    process,
 };
 
-fn main() {
-    let stdin = io::stdin();
-    let stdin = stdin.lock();
-    let freq_steps = stdin
-        .lines()
-        .map(|line| line.unwrap().trim().parse())
-        .collect::<Result<Vec<i32>, _>>()
-        .unwrap();
+/// A set of frequencies that avoids hashing within a fixed window.
+///
+/// Frequencies inside the window are tracked in a bitmap; all others
+/// go into a `HashSet`.
+struct SeenFrequencies {
+    window: Range<i32>,
+    in_window: Vec<bool>,
+    outside: HashSet<i32>,
+}
+
+impl SeenFrequencies {
+    fn new(window: Range<i32>) -> Self {
+        let len = (window.end - window.start).max(0) as usize;
+        SeenFrequencies {
+            window,
+            in_window: vec![false; len],
+            outside: HashSet::new(),
+        }
+    }
+
+    /// Adds a frequency and returns true if it was not seen before.
+    fn insert(&mut self, frequency: i32) -> bool {
+        if self.window.contains(&frequency) {
+            let seen = &mut self.in_window[(frequency - self.window.start) as usize];
+            !std::mem::replace(seen, true)
+        } else {
+            self.outside.insert(frequency)
+        }
+    }
+}
+
+/// Finds the first frequency that is reached twice.
+///
+/// The steps are cloned for each pass, so they are only ever consumed
+/// as far as needed. This loops forever if no frequency ever repeats;
+/// use `first_repeat` if that might be the case.
+///
+/// # Panics
+///
+/// This panics if there are no steps at all.
+#[allow(dead_code)]
+fn find_first_repeated_frequency<I>(steps: I) -> i32
+where
+    I: IntoIterator<Item = i32> + Clone,
+{
+    assert!(steps.clone().into_iter().next().is_some(), "no steps");
     let mut seen = HashSet::new();
     let mut current = 0i32;
-    for step in freq_steps.iter().cycle() {
+    for step in iter::repeat(steps).flatten() {
         if !seen.insert(current) {
             break;
         }
         current += step;
     }
-    println!("result: {}", current);
+    current
+}
+
+/// Where the first repeated frequency was reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct RepeatInfo {
+    frequency: i32,
+    /// The number of steps applied in the current pass.
+    index: usize,
+    /// The number of times that all steps have been applied.
+    passes: usize,
+}
+
+impl RepeatInfo {
+    fn new(frequency: i32, num_steps: usize, num_deltas: usize) -> Self {
+        RepeatInfo {
+            frequency,
+            index: num_steps % num_deltas,
+            passes: num_steps / num_deltas,
+        }
+    }
+
+    fn num_steps(&self, num_deltas: usize) -> usize {
+        self.passes * num_deltas + self.index
+    }
+}
+
+/// Finds where the first repeated frequency is reached.
+///
+/// Rather than cycling through the steps until something repeats, this
+/// only simulates the first pass. Every later pass visits the same
+/// frequencies shifted by the net sum of all steps. Hence, if the net
+/// sum is nonzero, a frequency can only be revisited by one from the
+/// same residue class modulo the net sum. Within each class, the
+/// earliest revisit comes from the nearest neighbor on the side that
+/// the frequencies drift away from.
+///
+/// If the net sum is nonzero and no two frequencies of the first pass
+/// share a residue class, nothing ever repeats and this returns `None`.
+fn first_repeated(steps: &[i32]) -> Option<RepeatInfo> {
+    let mut first_pass = HashMap::new();
+    let mut current = 0i32;
+    for (i, &step) in steps.iter().enumerate() {
+        if first_pass.insert(current, i).is_some() {
+            return Some(RepeatInfo::new(current, i, steps.len()));
+        }
+        current += step;
+    }
+    let net = current;
+    if steps.is_empty() {
+        return None;
+    } else if net == 0 {
+        return Some(RepeatInfo::new(0, steps.len(), steps.len()));
+    }
+    let mut classes = HashMap::<i32, Vec<(i32, usize)>>::new();
+    for (&frequency, &i) in &first_pass {
+        let class = classes.entry(frequency.rem_euclid(net.abs())).or_default();
+        class.push((frequency, i));
+    }
+    for class in classes.values_mut() {
+        class.sort_by_key(|&(frequency, _)| frequency * net.signum());
+    }
+    classes
+        .values()
+        .flat_map(|class| class.windows(2))
+        .map(|pair| {
+            let ((from, i), (to, _)) = (pair[0], pair[1]);
+            let passes = ((to - from) / net) as usize;
+            (to, passes * steps.len() + i)
+        })
+        .min_by_key(|&(_, num_steps)| num_steps)
+        .map(|(frequency, num_steps)| RepeatInfo::new(frequency, num_steps, steps.len()))
+}
+
+/// Like `first_repeated`, but simulates the steps one by one.
+///
+/// Frequencies within `window` are remembered in a bitmap, which is a
+/// lot faster than hashing if the input stays within a small range.
+///
+/// To not loop forever, the number of passes is bounded: Each pass
+/// shifts all frequencies by the net sum, so a frequency of the first
+/// pass can only be revisited within `(max - min) / net` passes, where
+/// `max` and `min` are the extremes of the first pass.
+#[allow(dead_code)]
+fn first_repeated_fast(steps: &[i32], window: Range<i32>) -> Option<RepeatInfo> {
+    let net = steps.iter().sum::<i32>();
+    let max_passes = if net == 0 {
+        2
+    } else {
+        let partial_sums = steps.iter().scan(0, |current, step| {
+            *current += step;
+            Some(*current)
+        });
+        let (min, max) = partial_sums.fold((0, 0), |(min, max), f| (min.min(f), max.max(f)));
+        ((max - min) / net.abs() + 2) as usize
+    };
+    let mut seen = SeenFrequencies::new(window);
+    let mut current = 0i32;
+    let num_deltas = steps.len();
+    let steps = steps.iter().cycle().take(max_passes * num_deltas + 1);
+    for (num_steps, &step) in steps.enumerate() {
+        if !seen.insert(current) {
+            return Some(RepeatInfo::new(current, num_steps, num_deltas));
+        }
+        current += step;
+    }
+    None
+}
+
+/// Yields every frequency at the moment it is reached the second time.
+///
+/// The steps are only applied as the iterator is advanced. The only
+/// work done up front is checking whether anything repeats at all. If
+/// not, the iterator is empty. If the steps sum up to zero, the
+/// iterator ends after the second pass, when all frequencies have been
+/// reached twice. Otherwise, it never ends.
+#[allow(dead_code)]
+fn repeated_frequencies(steps: Vec<i32>) -> impl Iterator<Item = i32> {
+    let max_steps = match first_repeated(&steps) {
+        None => 0,
+        Some(_) if steps.iter().sum::<i32>() == 0 => 2 * steps.len(),
+        Some(_) => usize::MAX,
+    };
+    let partial_sums = steps.into_iter().cycle().scan(0, |current, step| {
+        *current += step;
+        Some(*current)
+    });
+    let mut seen = HashSet::new();
+    let mut repeated = HashSet::new();
+    iter::once(0)
+        .chain(partial_sums)
+        .take(max_steps.saturating_add(1))
+        .filter(move |&frequency| !seen.insert(frequency) && repeated.insert(frequency))
+}
+
+/// Finds the first frequency that is reached twice.
+///
+/// See `first_repeated` for when this returns `None`.
+#[allow(dead_code)]
+fn first_repeat(steps: &[i32]) -> Option<i32> {
+    first_repeated(steps).map(|info| info.frequency)
+}
+
+fn main() {
+    let stdin = io::stdin();
+    let freq_steps = parse_steps(stdin.lock()).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    });
+    match first_repeated(&freq_steps) {
+        Some(info) => {
+            println!("result: {}", info.frequency);
+            println!(
+                "reached after {} passes plus {} steps ({} in total)",
+                info.passes,
+                info.index,
+                info.num_steps(freq_steps.len())
+            );
+        },
+        None => println!("no repeat possible"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_examples() {
+        let examples: &[(&[i32], i32)] = &[
+            (&[1, -2, 3, 1], 2),
+            (&[1, -1], 0),
+            (&[3, 3, 4, -2, -4], 10),
+            (&[-6, 3, 8, 5, -6], 5),
+            (&[7, 7, -2, -7, -4], 14),
+        ];
+        for &(steps, expected) in examples {
+            assert_eq!(
+                find_first_repeated_frequency(steps.iter().cloned()),
+                expected
+            );
+            assert_eq!(find_first_repeated_frequency(steps.to_vec()), expected);
+            assert_eq!(first_repeat(steps), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_first_repeated() {
+        let num_steps = |steps: &[i32]| first_repeated(steps).map(|i| i.num_steps(steps.len()));
+        assert_eq!(num_steps(&[7, 7, -2, -7, -4]), Some(13));
+        assert_eq!(num_steps(&[1, -1]), Some(2));
+        assert_eq!(num_steps(&[1, -2, 3, 1]), Some(6));
+    }
+
+    #[test]
+    fn test_repeat_info() {
+        // 0, 7, 14, 12, 5 | 1, 8, 15, 13, 6 | 2, 9, 16, 14
+        let info = RepeatInfo {
+            frequency: 14,
+            index: 3,
+            passes: 2,
+        };
+        assert_eq!(first_repeated(&[7, 7, -2, -7, -4]), Some(info));
+        // 0, 1, -1, 2 | 3, 4, 2
+        let info = RepeatInfo {
+            frequency: 2,
+            index: 2,
+            passes: 1,
+        };
+        assert_eq!(first_repeated(&[1, -2, 3, 1]), Some(info));
+    }
+
+    #[test]
+    fn test_first_repeat() {
+        assert_eq!(first_repeat(&[1, -1]), Some(0));
+        assert_eq!(first_repeat(&[3, 3, 4, -2, -4]), Some(10));
+        assert_eq!(first_repeat(&[0]), Some(0));
+    }
+
+    #[test]
+    fn test_no_repeat() {
+        assert_eq!(first_repeat(&[]), None);
+        assert_eq!(first_repeat(&[1, 2, 3]), None);
+        assert_eq!(first_repeat(&[-1, -1]), None);
+    }
+
+    #[test]
+    fn test_against_simulation() {
+        let examples: &[&[i32]] = &[
+            &[1, -2, 3, 1],
+            &[7, 7, -2, -7, -4],
+            &[-7, -7, 2, 7, 4],
+            &[3, 3, 4, -2, -4],
+            &[-6, 3, 8, 5, -6],
+            &[10, -3, -3, -3, 2],
+            &[-10, 3, 3, 3, -2, 5],
+            &[5, 0],
+            &[1, 1, -3, 5, -12, 9, 2, -1],
+        ];
+        for &steps in examples {
+            let expected = find_first_repeated_frequency(steps.iter().cloned());
+            assert_eq!(first_repeat(steps), Some(expected), "{:?}", steps);
+        }
+    }
+
+    #[test]
+    fn test_fast() {
+        // Generate some pseudo-random steps that mostly cancel out.
+        let mut state = 12345u32;
+        let steps = (0..1000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as i32 % 2001 - 1000
+            })
+            .collect::<Vec<_>>();
+        let expected = first_repeated(&steps);
+        assert!(expected.is_some());
+        assert_eq!(first_repeated_fast(&steps, -100_000..100_000), expected);
+        assert_eq!(first_repeated_fast(&steps, -10..10), expected);
+        assert_eq!(first_repeated_fast(&steps, 0..0), expected);
+        for &steps in &[&[7, 7, -2, -7, -4][..], &[1, -1], &[1, 2, 3], &[]] {
+            assert_eq!(first_repeated_fast(steps, -20..20), first_repeated(steps));
+        }
+    }
+
+    #[test]
+    fn test_repeated_frequencies() {
+        let repeats = repeated_frequencies(vec![1, -2, 3, 1]);
+        assert_eq!(repeats.take(2).collect::<Vec<_>>(), [2, 5]);
+        let repeats = repeated_frequencies(vec![1, -1, 3, -3]);
+        assert_eq!(repeats.collect::<Vec<_>>(), [0, 1, 3]);
+        assert_eq!(repeated_frequencies(vec![1, 2]).next(), None);
+        assert_eq!(repeated_frequencies(vec![]).next(), None);
+    }
 }
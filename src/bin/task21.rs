@@ -1,33 +1,108 @@
-use std::io::{self, BufRead};
-
-type Grid = Box<[[i32; 300]; 300]>;
-
-fn power_levels(serial_number: u32) -> Grid {
-    let mut result = Box::new([[0i32; 300]; 300]);
-    for x in 1..=300 {
-        let rack = x + 10;
-        let rack_sq = rack * rack;
-        let rack_serial = serial_number * rack;
-        for y in 1..=300 {
-            let level_base = rack_sq * y + rack_serial;
-            let level = ((level_base / 100) % 10) as i32 - 5;
-            result[(x - 1) as usize][(y - 1) as usize] = level;
+use std::{
+    io::{self, BufRead},
+    ops::{Index, IndexMut},
+};
+
+const GRID_SIZE: usize = 300;
+
+/// A square grid of fuel cells, indexed by zero-based `(x, y)`.
+#[derive(Debug, Clone)]
+struct Grid {
+    contents: Vec<i32>,
+    size: usize,
+}
+
+impl Grid {
+    fn new(size: usize) -> Self {
+        Grid {
+            contents: vec![0; size * size],
+            size,
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Index<(usize, usize)> for Grid {
+    type Output = i32;
+
+    fn index(&self, (x, y): (usize, usize)) -> &Self::Output {
+        assert!(x < self.size && y < self.size);
+        &self.contents[x * self.size + y]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Grid {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Self::Output {
+        assert!(x < self.size && y < self.size);
+        &mut self.contents[x * self.size + y]
+    }
+}
+
+/// Summed-area table of a `Grid`.
+///
+/// The entry at `(x, y)` holds the sum of all cells `grid[(i, j)]` with
+/// `i < x` and `j < y`. This is one row and column larger than the
+/// grid so that the first row and column are all zero.
+type SummedAreaTable = Grid;
+
+/// The power level of the fuel cell at the one-based coordinates.
+fn cell_power(x: i32, y: i32, serial_number: u32) -> i32 {
+    let rack = x + 10;
+    let level_base = (rack * y + serial_number as i32) * rack;
+    (level_base / 100) % 10 - 5
+}
+
+fn power_levels(serial_number: u32, size: usize) -> Grid {
+    let mut result = Grid::new(size);
+    for x in 0..size {
+        for y in 0..size {
+            result[(x, y)] = cell_power(x as i32 + 1, y as i32 + 1, serial_number);
         }
     }
     result
 }
 
-fn sum_of_square(grid: &Grid, topleft: (usize, usize), size: usize) -> i32 {
-    (0..size)
-        .flat_map(|x| (0..size).map(move |y| grid[topleft.0 + x][topleft.1 + y]))
-        .sum()
+fn summed_area_table(grid: &Grid) -> SummedAreaTable {
+    let mut table = Grid::new(grid.size() + 1);
+    for x in 0..grid.size() {
+        for y in 0..grid.size() {
+            table[(x + 1, y + 1)] =
+                grid[(x, y)] + table[(x, y + 1)] + table[(x + 1, y)] - table[(x, y)];
+        }
+    }
+    table
+}
+
+/// Sums up the square of the given size in O(1).
+///
+/// Summing the cells one by one made the search over all sizes take
+/// several minutes; with the table, it finishes in about a second.
+fn sum_of_square(table: &SummedAreaTable, topleft: (usize, usize), size: usize) -> i32 {
+    let (x, y) = topleft;
+    table[(x + size, y + size)] - table[(x, y + size)] - table[(x + size, y)] + table[(x, y)]
 }
 
-fn find_max_square(grid: &Grid, size: usize) -> (usize, usize, i32) {
-    (0..301 - size)
-        .flat_map(|x| (0..301 - size).map(move |y| (x, y, sum_of_square(grid, (x, y), size))))
+/// The top-left corner, size, and total fuel of a square.
+///
+/// Coordinates are one-based, as in the puzzle.
+type Square = (usize, usize, usize, i32);
+
+fn find_max_square(table: &SummedAreaTable, size: usize) -> Square {
+    let positions = table.size() - size;
+    (0..positions)
+        .flat_map(|x| (0..positions).map(move |y| (x, y, sum_of_square(table, (x, y), size))))
         .max_by_key(|&(_, _, fuel)| fuel)
-        .map(|(x, y, fuel)| (x + 1, y + 1, fuel))
+        .map(|(x, y, fuel)| (x + 1, y + 1, size, fuel))
+        .unwrap()
+}
+
+fn find_max_square_of_any_size(table: &SummedAreaTable) -> Square {
+    (1..table.size())
+        .map(|size| find_max_square(table, size))
+        .max_by_key(|&(_, _, _, fuel)| fuel)
         .unwrap()
 }
 
@@ -38,15 +113,69 @@ fn main() {
         stdin.lock().read_line(&mut buf).unwrap();
         buf.trim().parse::<u32>().unwrap()
     };
-    let levels = power_levels(serial_number);
-    let (x, y, _) = find_max_square(&levels, 3);
-    println!("max fuel at size 3: {},{}", x, y);
-    let (x, y, size, _) = (0..301usize)
-        .map(|size| {
-            let (x, y, fuel) = find_max_square(&levels, size);
-            (x, y, size, fuel)
-        })
-        .max_by_key(|&(_, _, _, fuel)| fuel)
-        .unwrap();
-    println!("max fuel: {},{},{}", x, y, size);
+    let table = summed_area_table(&power_levels(serial_number, GRID_SIZE));
+    let (x, y, _, fuel) = find_max_square(&table, 3);
+    println!("max fuel at size 3: {},{} (fuel = {})", x, y, fuel);
+    let (x, y, size, fuel) = find_max_square_of_any_size(&table);
+    println!("max fuel: {},{},{} (fuel = {})", x, y, size, fuel);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_power() {
+        assert_eq!(cell_power(3, 5, 8), 4);
+        assert_eq!(cell_power(122, 79, 57), -5);
+        assert_eq!(cell_power(217, 196, 39), 0);
+        assert_eq!(cell_power(101, 153, 71), 4);
+    }
+
+    #[test]
+    fn test_sum_of_square() {
+        let grid = power_levels(18, GRID_SIZE);
+        let table = summed_area_table(&grid);
+        let naive = (0..3)
+            .flat_map(|x| (0..3).map(move |y| (x, y)))
+            .map(|(x, y)| grid[(32 + x, 44 + y)])
+            .sum::<i32>();
+        assert_eq!(sum_of_square(&table, (32, 44), 3), naive);
+    }
+
+    #[test]
+    fn test_size_3() {
+        let table = summed_area_table(&power_levels(18, GRID_SIZE));
+        assert_eq!(find_max_square(&table, 3), (33, 45, 3, 29));
+        let table = summed_area_table(&power_levels(42, GRID_SIZE));
+        assert_eq!(find_max_square(&table, 3), (21, 61, 3, 30));
+    }
+
+    #[test]
+    fn test_any_size() {
+        let table = summed_area_table(&power_levels(18, GRID_SIZE));
+        assert_eq!(find_max_square_of_any_size(&table), (90, 269, 16, 113));
+    }
+
+    #[test]
+    fn test_size_3_fuel() {
+        let table = summed_area_table(&power_levels(18, GRID_SIZE));
+        let (_, _, _, fuel) = find_max_square(&table, 3);
+        assert_eq!(fuel, 29);
+    }
+
+    #[test]
+    fn test_small_grid() {
+        // The top-left corner of the grid for serial number 18:
+        //
+        //     -2 -2 -1 -1
+        //     -1  0  0  1
+        //      0  1  2  3
+        //      1  2  4 -5
+        //      3  4 -5 -3
+        let table = summed_area_table(&power_levels(18, 10));
+        assert_eq!(table.size(), 11);
+        assert_eq!(find_max_square(&table, 3), (1, 3, 3, 12));
+        assert_eq!(find_max_square_of_any_size(&table), (1, 3, 3, 12));
+    }
 }
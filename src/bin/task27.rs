@@ -1,73 +1,80 @@
-use std::io::{self, Read};
+use std::{
+    fmt::{self, Display},
+    io::{self, Read},
+    num::{IntErrorKind, ParseIntError},
+    process,
+};
 
-enum UpdateKind {
-    PushedOne,
-    PushedTwo,
-}
-
-struct Scoreboard {
+pub struct Scoreboard {
     scores: Vec<u8>,
-    elves: (usize, usize),
+    elves: Vec<usize>,
 }
 
 impl Scoreboard {
-    fn new(max_num_recipes: usize) -> Self {
-        let mut scores = Vec::with_capacity(max_num_recipes);
-        scores.push(3);
-        scores.push(7);
-        let elves = (0, 1);
+    pub fn new(max_num_recipes: usize) -> Self {
+        Scoreboard::with_config(&[3, 7], 2, max_num_recipes)
+    }
+
+    /// Creates a board with custom initial recipes and number of elves.
+    ///
+    /// The elves start out on the first `num_elves` recipes. At most 11
+    /// elves are supported so that their combined score never has more
+    /// than two digits.
+    pub fn with_config(initial: &[u8], num_elves: usize, capacity: usize) -> Self {
+        assert!(num_elves > 0 && num_elves <= 11);
+        assert!(initial.len() >= num_elves);
+        assert!(initial.iter().all(|&score| score < 10));
+        let mut scores = Vec::with_capacity(capacity);
+        scores.extend_from_slice(initial);
+        let elves = (0..num_elves).collect();
         Scoreboard { scores, elves }
     }
 
-    fn update(&mut self) -> UpdateKind {
-        let recipes = (self.scores[self.elves.0], self.scores[self.elves.1]);
-        let combined = recipes.0 + recipes.1;
-        debug_assert!(combined <= 18);
-        let kind = if combined < 10 {
+    pub fn update(&mut self) {
+        let combined = self.elves.iter().map(|&elf| self.scores[elf]).sum::<u8>();
+        debug_assert!(combined <= 99);
+        if combined < 10 {
             self.scores.push(combined);
-            UpdateKind::PushedOne
         } else {
             self.scores.push(combined / 10);
             self.scores.push(combined % 10);
-            UpdateKind::PushedTwo
-        };
-        self.elves.0 = (self.elves.0 + recipes.0 as usize + 1) % self.scores.len();
-        self.elves.1 = (self.elves.1 + recipes.1 as usize + 1) % self.scores.len();
-        kind
+        }
+        let len = self.scores.len();
+        for elf in &mut self.elves {
+            *elf = (*elf + self.scores[*elf] as usize + 1) % len;
+        }
     }
 
-    fn update_n_recipes(&mut self, recipes: usize) {
+    pub fn update_n_recipes(&mut self, recipes: usize) {
         while self.scores.len() < recipes {
             self.update();
         }
     }
 
-    fn scores(&self, start: usize, len: usize) -> Option<&[u8]> {
+    pub fn scores(&self, start: usize, len: usize) -> Option<&[u8]> {
         self.scores.get(start..).and_then(|s| s.get(..len))
     }
 
-    fn update_until_sequence(&mut self, needle: &[u8]) -> usize {
-        for i in 0..self.scores.len() {
-            let scores = match self.scores(i, needle.len()) {
-                Some(scores) => scores,
-                None => break,
-            };
-            if scores == needle {
-                return i;
-            }
-        }
+    /// Iterates over all scores on the board so far.
+    pub fn iter_digits(&self) -> impl Iterator<Item = u8> + '_ {
+        self.scores.iter().cloned()
+    }
+
+    /// Updates the board until `needle` appears and returns its position.
+    ///
+    /// Every window of `needle.len()` scores is checked exactly once,
+    /// as soon as it is complete. This way, the earliest match is found
+    /// no matter whether an update pushed one or two scores.
+    pub fn update_until_sequence(&mut self, needle: &[u8]) -> usize {
+        let mut start = 0;
         loop {
-            let kind = self.update();
-            let i = self.scores.len().saturating_sub(needle.len());
-            if let UpdateKind::PushedTwo = kind {
-                let i = i.saturating_sub(1);
-                if self.scores(i, needle.len()) == Some(needle) {
-                    return i;
+            while let Some(window) = self.scores(start, needle.len()) {
+                if window == needle {
+                    return start;
                 }
+                start += 1;
             }
-            if self.scores(i, needle.len()) == Some(needle) {
-                return i;
-            }
+            self.update();
         }
     }
 }
@@ -93,31 +100,79 @@ fn write_scores(scores: &[u8]) -> String {
     buf
 }
 
-fn to_digits(s: &str) -> Vec<u8> {
-    s.chars().map(|c| c.to_digit(10).unwrap() as u8).collect()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadDigit;
+
+/// Error that occurs when the puzzle input can't be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BadInput {
+    NotDigits,
+    TooManyRecipes,
 }
 
-fn main() {
-    let (stop, needle) = {
-        let mut buf = String::new();
-        io::stdin().read_to_string(&mut buf).unwrap();
-        let buf = buf.trim();
-        let stop = buf.trim().parse::<usize>().unwrap();
-        let needle = to_digits(buf);
-        (stop, needle)
-    };
-    {
-        let mut board = Scoreboard::new(stop + 20);
-        board.update_n_recipes(stop + 10);
-        println!("scores: {}", write_scores(&board.scores[stop..stop + 10]));
-    }
-    {
-        let mut board = Scoreboard::new(1000);
-        let i = board.update_until_sequence(&needle);
-        println!("pos: {}", i);
+impl Display for BadInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BadInput::NotDigits => write!(f, "input is not a sequence of digits"),
+            BadInput::TooManyRecipes => write!(f, "number of recipes is too large"),
+        }
+    }
+}
+
+impl From<BadDigit> for BadInput {
+    fn from(_: BadDigit) -> Self {
+        BadInput::NotDigits
     }
 }
 
+impl From<ParseIntError> for BadInput {
+    fn from(err: ParseIntError) -> Self {
+        match err.kind() {
+            IntErrorKind::PosOverflow => BadInput::TooManyRecipes,
+            _ => BadInput::NotDigits,
+        }
+    }
+}
+
+fn to_digits(s: &str) -> Result<Vec<u8>, BadDigit> {
+    s.chars()
+        .map(|c| c.to_digit(10).map(|d| d as u8).ok_or(BadDigit))
+        .collect()
+}
+
+/// The largest board that `solve_both` is willing to allocate.
+const MAX_NUM_RECIPES: usize = 1_000_000_000;
+
+/// Answers both parts of the puzzle with a single scoreboard.
+///
+/// The input is read both as a number of recipes and as a sequence of
+/// scores. Because the board only ever grows, whichever part needs
+/// fewer recipes is answered for free.
+fn solve_both(input: &str) -> Result<(String, usize), BadInput> {
+    let needle = to_digits(input)?;
+    let stop = input.parse::<usize>()?;
+    let capacity = stop
+        .checked_add(20)
+        .filter(|&capacity| capacity <= MAX_NUM_RECIPES)
+        .ok_or(BadInput::TooManyRecipes)?;
+    let mut board = Scoreboard::new(capacity);
+    let pos = board.update_until_sequence(&needle);
+    board.update_n_recipes(stop + 10);
+    let scores = write_scores(board.scores(stop, 10).unwrap());
+    Ok((scores, pos))
+}
+
+fn main() {
+    let mut buf = String::new();
+    io::stdin().read_to_string(&mut buf).unwrap();
+    let (scores, pos) = solve_both(buf.trim()).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    });
+    println!("scores: {}", scores);
+    println!("pos: {}", pos);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -140,4 +195,76 @@ mod tests {
         assert_eq!(board.update_until_sequence(&[9, 2, 5, 1, 0]), 18);
         assert_eq!(board.update_until_sequence(&[5, 9, 4, 1, 4]), 2018);
     }
+
+    #[test]
+    fn test_two_digit_push() {
+        // The first update pushes the 1 and 0 at positions 2 and 3.
+        let mut board = Scoreboard::new(100);
+        assert_eq!(board.update_until_sequence(&[3, 7, 1]), 0);
+        assert_eq!(board.update_until_sequence(&[7, 1, 0]), 1);
+        assert_eq!(board.update_until_sequence(&[1, 0]), 2);
+        assert_eq!(board.update_until_sequence(&[0, 1]), 3);
+    }
+
+    #[test]
+    fn test_earliest_match() {
+        let mut full = Scoreboard::new(200);
+        full.update_n_recipes(200);
+        for start in 0..100 {
+            let needle = full.scores(start, 3).unwrap();
+            let earliest = (0..=start)
+                .find(|&i| full.scores(i, 3) == Some(needle))
+                .unwrap();
+            let mut board = Scoreboard::new(200);
+            assert_eq!(board.update_until_sequence(needle), earliest);
+        }
+    }
+
+    #[test]
+    fn test_iter_digits() {
+        let mut board = Scoreboard::new(20);
+        board.update_n_recipes(19);
+        let digits = board.iter_digits().take(10).collect::<Vec<_>>();
+        assert_eq!(write_scores(&digits), "3710101245");
+        let digits = board.iter_digits().skip(9).take(10).collect::<Vec<_>>();
+        assert_eq!(write_scores(&digits), "5158916779");
+    }
+
+    #[test]
+    fn test_solve_both() {
+        assert_eq!(solve_both("9"), Ok(("5158916779".to_owned(), 13)));
+        assert_eq!(solve_both("2018"), Ok(("5941429882".to_owned(), 86764)));
+        assert_eq!(solve_both("51589"), Ok(("3910137144".to_owned(), 9)));
+        assert_eq!(solve_both("59414"), Ok(("5131221087".to_owned(), 2018)));
+        assert_eq!(solve_both("12a4"), Err(BadInput::NotDigits));
+        assert_eq!(solve_both(""), Err(BadInput::NotDigits));
+        assert_eq!(
+            solve_both("123456789012345678901234"),
+            Err(BadInput::TooManyRecipes)
+        );
+        assert_eq!(
+            solve_both("9999999999999999999"),
+            Err(BadInput::TooManyRecipes)
+        );
+    }
+
+    #[test]
+    fn test_to_digits() {
+        assert_eq!(to_digits("59414"), Ok(vec![5, 9, 4, 1, 4]));
+        assert_eq!(to_digits("12a4"), Err(BadDigit));
+    }
+
+    #[test]
+    fn test_with_config() {
+        let mut classic = Scoreboard::with_config(&[3, 7], 2, 20);
+        classic.update_n_recipes(20);
+        assert_eq!(
+            classic.scores(0, 10),
+            Some(&[3, 7, 1, 0, 1, 0, 1, 2, 4, 5][..])
+        );
+        let mut three_elves = Scoreboard::with_config(&[3, 7, 1], 3, 20);
+        three_elves.update_n_recipes(20);
+        assert_eq!(three_elves.scores(0, 4), Some(&[3, 7, 1, 1][..]));
+        assert_ne!(three_elves.scores(0, 20), classic.scores(0, 20));
+    }
 }
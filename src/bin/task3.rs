@@ -1,32 +1,190 @@
-use std::io::BufRead;
+use std::{
+    collections::HashMap,
+    env,
+    io::{self, BufRead},
+};
 
-fn tally_letters(bytes: &[u8]) -> [u8; 26] {
-    let mut tally = [0; 26];
-    for byte in bytes {
-        let i = match byte {
-            b'a'..=b'z' => (byte - b'a') as usize,
-            _ => panic!("bad letter: {}", byte),
-        };
-        tally[i] += 1;
+/// Counts how often each byte appears in a box ID.
+///
+/// Any byte is accepted, including digits and uppercase letters. Counts
+/// saturate at 255, which is never mistaken for a doublet or triplet.
+fn tally_letters(bytes: &[u8]) -> HashMap<u8, u8> {
+    let mut tally = HashMap::new();
+    for &byte in bytes {
+        let count = tally.entry(byte).or_insert(0u8);
+        *count = count.saturating_add(1);
     }
     tally
 }
 
-fn main() {
-    let stdin = std::io::stdin();
-    let stdin = stdin.lock();
+/// Returns true if any character appears exactly `n` times.
+fn has_exactly(tally: &HashMap<u8, u8>, n: u8) -> bool {
+    tally.values().any(|&count| count == n)
+}
+
+/// Returns whether any letter appears exactly twice and thrice.
+fn has_doublet_and_triplet(id: &str) -> (bool, bool) {
+    let tally = tally_letters(id.trim().as_bytes());
+    (has_exactly(&tally, 2), has_exactly(&tally, 3))
+}
+
+/// Counts the box IDs with any letter exactly twice and thrice.
+fn checksum_components<'a>(ids: impl Iterator<Item = &'a str>) -> (u32, u32) {
     let mut num_doublets = 0;
     let mut num_triplets = 0;
-    for line in stdin.lines() {
-        let line = line.unwrap();
-        let tally = tally_letters(line.trim().as_bytes());
-        if tally.iter().any(|&x| x == 2) {
-            num_doublets += 1;
+    for id in ids {
+        let (has_doublet, has_triplet) = has_doublet_and_triplet(id);
+        num_doublets += has_doublet as u32;
+        num_triplets += has_triplet as u32;
+    }
+    (num_doublets, num_triplets)
+}
+
+fn checksum<'a>(ids: impl Iterator<Item = &'a str>) -> u32 {
+    let (num_doublets, num_triplets) = checksum_components(ids);
+    num_doublets * num_triplets
+}
+
+/// Returns how often each letter a–z appears at most in a single ID.
+fn max_letter_counts<'a>(ids: impl Iterator<Item = &'a str>) -> [u8; 26] {
+    let mut maxima = [0; 26];
+    for id in ids {
+        for (&byte, &count) in &tally_letters(id.trim().as_bytes()) {
+            if let b'a'..=b'z' = byte {
+                let max = &mut maxima[(byte - b'a') as usize];
+                *max = (*max).max(count);
+            }
         }
-        if tally.iter().any(|&x| x == 3) {
-            num_triplets += 1;
+    }
+    maxima
+}
+
+/// Reads one box ID per line.
+///
+/// Surrounding whitespace, including a Windows-style `\r`, is removed
+/// and empty lines are skipped.
+fn read_ids<R: BufRead>(r: R) -> io::Result<Vec<String>> {
+    let mut ids = Vec::new();
+    for line in r.lines() {
+        let line = line?;
+        let id = line.trim();
+        if !id.is_empty() {
+            ids.push(id.to_owned());
+        }
+    }
+    Ok(ids)
+}
+
+fn main() {
+    let stdin = io::stdin();
+    let lines = read_ids(stdin.lock()).unwrap();
+    if env::args().skip(1).any(|arg| arg == "--verbose") {
+        for line in &lines {
+            let (has_doublet, has_triplet) = has_doublet_and_triplet(line);
+            let doublet = if has_doublet { "[double]" } else { "" };
+            let triplet = if has_triplet { "[triple]" } else { "" };
+            println!("{} {}{}", line, doublet, triplet);
         }
     }
-    let checksum = num_doublets * num_triplets;
-    println!("checksum: {}", checksum);
+    println!("checksum: {}", checksum(lines.iter().map(String::as_str)));
+    println!("max letter counts:");
+    let maxima = max_letter_counts(lines.iter().map(String::as_str));
+    for (letter, max) in (b'a'..=b'z').zip(maxima.iter()) {
+        println!("{}: {}", letter as char, max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_ids() -> Vec<String> {
+        [
+            "abcdef", "bababc", "abbcde", "abcccd", "aabcdd", "abcdee", "ababab",
+        ]
+        .iter()
+        .map(|&id| id.to_owned())
+        .collect()
+    }
+
+    #[test]
+    fn test_checksum_components() {
+        let ids = example_ids();
+        assert_eq!(checksum_components(ids.iter().map(String::as_str)), (4, 3));
+    }
+
+    #[test]
+    fn test_checksum() {
+        let ids = example_ids();
+        assert_eq!(checksum(ids.iter().map(String::as_str)), 12);
+        assert_eq!(checksum(["abcdef", "aabbcc"].iter().cloned()), 0);
+    }
+
+    #[test]
+    fn test_has_doublet_and_triplet() {
+        let flags = example_ids()
+            .iter()
+            .map(|id| has_doublet_and_triplet(id))
+            .collect::<Vec<_>>();
+        let expected = [
+            (false, false),
+            (true, true),
+            (true, false),
+            (false, true),
+            (true, false),
+            (true, false),
+            (false, true),
+        ];
+        assert_eq!(flags, expected);
+    }
+
+    #[test]
+    fn test_max_letter_counts() {
+        let maxima = max_letter_counts(["abbz", "aaab", "Z1"].iter().cloned());
+        let mut expected = [0; 26];
+        expected[0] = 3;
+        expected[1] = 2;
+        expected[25] = 1;
+        assert_eq!(maxima, expected);
+    }
+
+    #[test]
+    fn test_read_ids() {
+        let ids = read_ids("aabc\r\n\n".as_bytes()).unwrap();
+        assert_eq!(ids, ["aabc"]);
+        assert_eq!(checksum_components(ids.iter().map(String::as_str)), (1, 0));
+        let ids = read_ids("abc\r\n\n  \r\nbbb \n".as_bytes()).unwrap();
+        assert_eq!(ids, ["abc", "bbb"]);
+    }
+
+    #[test]
+    fn test_has_exactly() {
+        let tally = tally_letters(b"aabbbcccc");
+        assert!(!has_exactly(&tally, 1));
+        assert!(has_exactly(&tally, 2));
+        assert!(has_exactly(&tally, 3));
+        assert!(has_exactly(&tally, 4));
+        assert!(!has_exactly(&tally, 5));
+    }
+
+    #[test]
+    fn test_tally_letters() {
+        let tally = tally_letters(b"Ab1b1c1 ");
+        assert_eq!(tally[&b'A'], 1);
+        assert_eq!(tally[&b'b'], 2);
+        assert_eq!(tally[&b'1'], 3);
+        assert_eq!(tally[&b' '], 1);
+        assert_eq!(tally.get(&b'a'), None);
+        let ids = ["a1b1c", "abcde"];
+        assert_eq!(checksum_components(ids.iter().cloned()), (1, 0));
+    }
+
+    #[test]
+    fn test_mixed_case() {
+        let tally = tally_letters(b"aAbBa");
+        assert_eq!(tally[&b'a'], 2);
+        assert_eq!(tally[&b'A'], 1);
+        let ids = ["aAbBa", "AbAbA", "aAbB"];
+        assert_eq!(checksum_components(ids.iter().cloned()), (2, 1));
+    }
 }
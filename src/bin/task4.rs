@@ -1,51 +1,298 @@
-use std::io::BufRead;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    env,
+    io::BufRead,
+};
 
-fn differ_by_one(left: &[u8], right: &[u8]) -> bool {
-    let mut one_diff = false;
+/// Returns true if the IDs differ in at least one and at most `k`
+/// positions.
+///
+/// IDs of different lengths never count as similar.
+pub fn differ_by_at_most(left: &[u8], right: &[u8], k: usize) -> bool {
+    if left.len() != right.len() {
+        return false;
+    }
+    let mut num_diffs = 0;
     for (&l, &r) in left.iter().zip(right.iter()) {
         if l != r {
-            if one_diff {
+            if num_diffs == k {
                 return false;
             } else {
-                one_diff = true;
+                num_diffs += 1;
             }
         }
     }
-    true
+    num_diffs > 0
+}
+
+/// Returns true if the IDs differ in exactly one position.
+///
+/// IDs of different lengths never count as similar.
+pub fn differ_by_one(left: &[u8], right: &[u8]) -> bool {
+    differ_by_at_most(left, right, 1)
+}
+
+/// Iterates over all pairs of IDs that differ in at most `k` positions.
+///
+/// Each pair consists of an ID and one that came before it. The pairs
+/// are sorted by the position of the first ID, then the second ID.
+fn similar_pairs<I>(lines: I, k: usize) -> impl Iterator<Item = (String, String)>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    let mut seen = Vec::<String>::with_capacity(250);
+    lines.flat_map(move |line| {
+        let line = line.as_ref().trim().to_owned();
+        let pairs = seen
+            .iter()
+            .filter(|seen| differ_by_at_most(line.as_bytes(), seen.as_bytes(), k))
+            .map(|seen| (line.clone(), seen.clone()))
+            .collect::<Vec<_>>();
+        seen.push(line);
+        pairs
+    })
+}
+
+/// Finds the first ID that differs from a previous one in at most `k`
+/// positions and returns both.
+pub fn find_within_k_differences<I>(lines: I, k: usize) -> Option<(String, String)>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    similar_pairs(lines, k).next()
 }
 
-fn find_similar<I>(lines: I) -> Option<(String, String)>
+pub fn find_similar<I>(lines: I) -> Option<(String, String)>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    find_within_k_differences(lines, 1)
+}
+
+/// Finds all pairs of IDs that differ in exactly one position.
+pub fn find_all_similar<I>(lines: I) -> Vec<(String, String)>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    similar_pairs(lines, 1).collect()
+}
+
+/// Like `find_similar`, but in roughly linear time.
+///
+/// For each position, every ID is hashed with that position cut out.
+/// Two IDs that differ in exactly that position collide. To return the
+/// same pair as `find_similar`, we record the index of each seen ID and
+/// pick the earliest one on multiple collisions.
+#[allow(dead_code)]
+fn find_similar_masked<I>(lines: I) -> Option<(String, String)>
 where
     I: Iterator,
     I::Item: AsRef<str>,
 {
     let mut seen = Vec::<String>::with_capacity(250);
+    let mut masked = HashMap::<(usize, Vec<u8>), usize>::new();
     for line in lines {
         let line = line.as_ref().trim();
-        for seen in &seen {
-            if differ_by_one(line.as_bytes(), seen.as_bytes()) {
-                let line = line.to_owned();
-                let seen = seen.clone();
-                return Some((line, seen));
-            }
+        let bytes = line.as_bytes();
+        let earliest = (0..bytes.len())
+            .filter_map(|pos| {
+                let key = (pos, [&bytes[..pos], &bytes[pos + 1..]].concat());
+                match masked.entry(key) {
+                    Entry::Occupied(entry) if seen[*entry.get()] != line => Some(*entry.get()),
+                    Entry::Occupied(_) => None,
+                    Entry::Vacant(entry) => {
+                        entry.insert(seen.len());
+                        None
+                    },
+                }
+            })
+            .min();
+        if let Some(i) = earliest {
+            return Some((line.to_owned(), seen[i].clone()));
         }
         seen.push(line.to_owned());
     }
     None
 }
 
-fn remove_differences(left: &str, right: &str) -> String {
+/// Lowercases the ID if `ignore_case` is set, otherwise returns it
+/// unchanged.
+fn normalize_case(id: &str, ignore_case: bool) -> String {
+    if ignore_case {
+        id.to_lowercase()
+    } else {
+        id.to_owned()
+    }
+}
+
+/// Returns the characters that both IDs have in the same position.
+///
+/// # Panics
+/// This panics if the IDs have different lengths, since there is no
+/// sensible way to line them up.
+pub fn remove_differences(left: &str, right: &str) -> String {
     use boolinator::Boolinator;
+    assert_eq!(
+        left.chars().count(),
+        right.chars().count(),
+        "IDs of different lengths: {:?} and {:?}",
+        left,
+        right
+    );
     left.chars()
         .zip(right.chars())
         .filter_map(|(l, r)| (l == r).as_some(l))
         .collect()
 }
 
+/// Finds two IDs that differ by one character and returns the rest.
+///
+/// Returns `None` if no two IDs are similar. IDs are consumed lazily;
+/// nothing after the second ID of the pair is read.
+fn common_letters_of_nearest<I>(lines: I) -> Option<String>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    let (left, right) = find_similar(lines)?;
+    Some(remove_differences(&left, &right))
+}
+
 fn main() {
     let stdin = std::io::stdin();
     let stdin = stdin.lock();
-    let lines = stdin.lines().map(|line| line.unwrap());
-    let (left, right) = find_similar(lines).unwrap();
-    println!("result: {}", remove_differences(&left, &right));
+    let ignore_case = env::args().skip(1).any(|arg| arg == "--ignore-case");
+    let lines = stdin
+        .lines()
+        .map(|line| normalize_case(&line.unwrap(), ignore_case));
+    match common_letters_of_nearest(lines) {
+        Some(result) => println!("result: {}", result),
+        None => println!("no result"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_ids() -> Vec<String> {
+        [
+            "abcde", "fghij", "klmno", "pqrst", "fguij", "axcye", "wvxyz",
+        ]
+        .iter()
+        .map(|&id| id.to_owned())
+        .collect()
+    }
+
+    #[test]
+    fn test_common_letters() {
+        let result = common_letters_of_nearest(example_ids().iter());
+        assert_eq!(result, Some("fgij".to_owned()));
+        assert_eq!(common_letters_of_nearest(["abc", "xyz"].iter()), None);
+    }
+
+    #[test]
+    fn test_differ_by_one() {
+        assert!(differ_by_one(b"fghij", b"fguij"));
+        assert!(!differ_by_one(b"fghij", b"fghij"));
+        assert!(!differ_by_one(b"abcde", b"axcye"));
+        assert!(!differ_by_one(b"abc", b"abcd"));
+        assert!(!differ_by_one(b"abcd", b"abc"));
+        assert!(!differ_by_one(b"abc", b"abxd"));
+    }
+
+    #[test]
+    fn test_masked() {
+        let ids = example_ids();
+        assert_eq!(find_similar_masked(ids.iter()), find_similar(ids.iter()));
+        let ids = ["abcx", "abxd", "abcd"];
+        assert_eq!(find_similar_masked(ids.iter()), find_similar(ids.iter()));
+        assert_eq!(
+            find_similar_masked(ids.iter()),
+            Some(("abcd".to_owned(), "abcx".to_owned()))
+        );
+        let ids = ["abcd", "abcd", "xyzw"];
+        assert_eq!(find_similar_masked(ids.iter()), None);
+        assert_eq!(find_similar(ids.iter()), None);
+    }
+
+    #[test]
+    fn test_k_differences() {
+        let ids = ["abcde", "axcye", "klmno"];
+        assert_eq!(find_within_k_differences(ids.iter(), 1), None);
+        assert_eq!(
+            find_within_k_differences(ids.iter(), 2),
+            Some(("axcye".to_owned(), "abcde".to_owned()))
+        );
+        assert!(differ_by_at_most(b"abcde", b"axcye", 2));
+        assert!(!differ_by_at_most(b"abcde", b"abcde", 2));
+    }
+
+    #[test]
+    fn test_different_lengths() {
+        // With a plain `zip`, these would look like zero or one
+        // differences apart.
+        let ids = ["abc", "abcd", "abxde", "ab"];
+        assert_eq!(find_similar(ids.iter()), None);
+        assert_eq!(find_similar_masked(ids.iter()), None);
+        assert_eq!(find_within_k_differences(ids.iter(), 2), None);
+    }
+
+    #[test]
+    fn test_find_all_similar() {
+        let ids = ["abcd", "abce", "xbce"];
+        let expected = vec![
+            ("abce".to_owned(), "abcd".to_owned()),
+            ("xbce".to_owned(), "abce".to_owned()),
+        ];
+        assert_eq!(find_all_similar(ids.iter()), expected);
+        assert_eq!(find_similar(ids.iter()), Some(expected[0].clone()));
+        assert_eq!(find_all_similar(example_ids().iter()).len(), 1);
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let compare = |l, r, ignore_case| {
+            let l = normalize_case(l, ignore_case);
+            let r = normalize_case(r, ignore_case);
+            differ_by_one(l.as_bytes(), r.as_bytes())
+        };
+        assert!(compare("ABc", "abd", true));
+        assert!(!compare("ABc", "abd", false));
+        // Without the flag, the case difference itself counts.
+        assert!(compare("ABc", "Abc", false));
+        assert!(!compare("ABc", "Abc", true));
+        let common = remove_differences(&normalize_case("FGhij", true), "fguij");
+        assert_eq!(common, "fgij");
+    }
+
+    #[test]
+    fn test_stops_at_first_match() {
+        // "fguij" is the fifth ID and completes the first pair.
+        let ids = example_ids()
+            .into_iter()
+            .take(5)
+            .chain(std::iter::repeat_with(|| -> String {
+                panic!("read past the first similar pair")
+            }));
+        let result = common_letters_of_nearest(ids);
+        assert_eq!(result, Some("fgij".to_owned()));
+    }
+
+    #[test]
+    fn test_remove_differences() {
+        assert_eq!(remove_differences("fghij", "fguij"), "fgij");
+        assert_eq!(remove_differences("abcde", "axcye"), "ace");
+        assert_eq!(remove_differences("abc", "abc"), "abc");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_remove_differences_of_different_lengths() {
+        remove_differences("fghij", "fgh");
+    }
 }
@@ -1,6 +1,5 @@
 use std::{
-    cmp::max,
-    collections::HashMap,
+    cmp::{max, min},
     io::{self, BufRead},
     num::ParseIntError,
     ops::{Index, IndexMut},
@@ -26,6 +25,16 @@ impl<T: Default + Clone> Map<T> {
         &self.contents
     }
 
+    /// Iterates over all cells in row-major order, together with their
+    /// coordinates `(x, y)`.
+    fn iter_coords(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        let width = self.width;
+        self.contents
+            .iter()
+            .enumerate()
+            .map(move |(i, value)| ((i % width, i / width), value))
+    }
+
     #[allow(dead_code)]
     fn with_rectangle<F: FnMut(&T)>(&self, rect: &Rectangle, mut f: F) {
         for y in 0..rect.height {
@@ -89,42 +98,74 @@ impl Rectangle {
         self.y + self.height
     }
 
+    /// Returns true if the rectangles share at least one cell.
+    ///
+    /// Right and bottom edges are exclusive, so rectangles that merely
+    /// touch don't intersect.
+    #[allow(dead_code)]
     fn intersects(&self, other: &Rectangle) -> bool {
-        self.right() >= other.left()
-            && other.right() >= self.left()
-            && self.bottom() >= other.top()
-            && other.bottom() >= self.top()
+        self.right() > other.left()
+            && other.right() > self.left()
+            && self.bottom() > other.top()
+            && other.bottom() > self.top()
+    }
+
+    /// Returns the number of cells that both rectangles cover.
+    #[allow(dead_code)]
+    fn intersection_area(&self, other: &Rectangle) -> usize {
+        let width = min(self.right(), other.right()).saturating_sub(max(self.left(), other.left()));
+        let height =
+            min(self.bottom(), other.bottom()).saturating_sub(max(self.top(), other.top()));
+        width * height
     }
 }
 
 impl std::str::FromStr for Rectangle {
     type Err = NotARectangle;
 
+    /// Parses a line of the form `#id @ x,y: wxh`.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = match s.split_at(1) {
-            ("#", s) => s,
+        let mut words = s.trim().split(' ');
+        let (id, pos, size) = match (
+            words.next(),
+            words.next(),
+            words.next(),
+            words.next(),
+            words.next(),
+        ) {
+            (Some(id), Some("@"), Some(pos), Some(size), None) => (id, pos, size),
+            _ => return Err(NotARectangle),
+        };
+        let id = match split_pair(id, '#')? {
+            ("", id) => id.parse()?,
+            _ => return Err(NotARectangle),
+        };
+        let (x, y) = match split_pair(pos, ':')? {
+            (pos, "") => split_pair(pos, ',')?,
             _ => return Err(NotARectangle),
         };
-        let pos = s.find(" @ ").ok_or(NotARectangle)?;
-        let (id, s) = (s[..pos].parse()?, &s[pos + 3..]);
-        let pos = s.find(",").ok_or(NotARectangle)?;
-        let (x, s) = (s[..pos].parse()?, &s[pos + 1..]);
-        let pos = s.find(": ").ok_or(NotARectangle)?;
-        let (y, s) = (s[..pos].parse()?, &s[pos + 2..]);
-        let pos = s.find("x").ok_or(NotARectangle)?;
-        let (width, height) = (s[..pos].parse()?, s[pos + 1..].parse()?);
+        let (width, height) = split_pair(size, 'x')?;
         let rect = Rectangle {
             id,
-            x,
-            y,
-            width,
-            height,
+            x: x.parse()?,
+            y: y.parse()?,
+            width: width.parse()?,
+            height: height.parse()?,
         };
         Ok(rect)
     }
 }
 
-#[derive(Debug, Clone)]
+/// Splits `s` at the first occurrence of `sep`.
+fn split_pair(s: &str, sep: char) -> Result<(&str, &str), NotARectangle> {
+    let mut parts = s.splitn(2, sep);
+    match (parts.next(), parts.next()) {
+        (Some(left), Some(right)) => Ok((left, right)),
+        _ => Err(NotARectangle),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct NotARectangle;
 
 impl From<ParseIntError> for NotARectangle {
@@ -133,41 +174,123 @@ impl From<ParseIntError> for NotARectangle {
     }
 }
 
-fn task_5(rectangles: &[Rectangle]) {
-    let (width, height) = rectangles.iter().fold((0, 0), |(width, height), rect| {
-        let width = max(width, rect.x + rect.width);
-        let height = max(height, rect.y + rect.height);
-        (width, height)
-    });
+/// Returns the size of the smallest map that contains all rectangles.
+fn bounding_size(rectangles: &[Rectangle]) -> (usize, usize) {
+    rectangles.iter().fold((0, 0), |(width, height), rect| {
+        (max(width, rect.right()), max(height, rect.bottom()))
+    })
+}
+
+/// Counts how many rectangles claim each cell by stamping them one by
+/// one.
+///
+/// This takes time proportional to the total area of all rectangles.
+#[allow(dead_code)]
+fn claim_counts_naive(rectangles: &[Rectangle]) -> Map<u16> {
+    let (width, height) = bounding_size(rectangles);
     let mut map = Map::<u16>::new(width, height);
     for rect in rectangles {
         map.with_rectangle_mut(rect, |count| *count += 1);
     }
-    let overlapped_area = map.cells().iter().cloned().filter(|&x| x > 1).count();
-    println!("overlapped area: {}", overlapped_area);
+    map
 }
 
-fn task_6(rectangles: &[Rectangle]) {
-    let mut seen = HashMap::new();
+/// Counts how many rectangles claim each cell using a difference array.
+///
+/// Each rectangle only marks its four corners; a 2D prefix sum then
+/// recovers the counts. This takes time proportional to the number of
+/// rectangles plus the size of the map.
+fn claim_counts(rectangles: &[Rectangle]) -> Map<u16> {
+    let (width, height) = bounding_size(rectangles);
+    let mut diffs = Map::<i32>::new(width + 1, height + 1);
     for rect in rectangles {
-        let intersects = seen
-            .iter_mut()
-            .fold(false, |intersects, (seen, seen_intersects)| {
-                if rect.intersects(seen) {
-                    *seen_intersects = true;
-                    true
-                } else {
-                    intersects
-                }
-            });
-        seen.insert(rect.clone(), intersects);
+        diffs[(rect.left(), rect.top())] += 1;
+        diffs[(rect.right(), rect.top())] -= 1;
+        diffs[(rect.left(), rect.bottom())] -= 1;
+        diffs[(rect.right(), rect.bottom())] += 1;
     }
-    println!("disjoint from all:");
-    for (rect, intersects) in &seen {
-        if !intersects {
-            println!("#{}", rect.id);
+    for y in 0..height {
+        for x in 1..width {
+            diffs[(x, y)] += diffs[(x - 1, y)];
+        }
+    }
+    for y in 1..height {
+        for x in 0..width {
+            diffs[(x, y)] += diffs[(x, y - 1)];
         }
     }
+    let mut map = Map::<u16>::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            map[(x, y)] = diffs[(x, y)] as u16;
+        }
+    }
+    map
+}
+
+/// Counts the cells claimed by more than one rectangle.
+fn overlapped_area(claims: &Map<u16>) -> usize {
+    claims.cells().iter().filter(|&&count| count > 1).count()
+}
+
+/// Returns the first cell with the highest claim count, and the count.
+///
+/// Returns `None` if the map is empty.
+fn most_contested(claims: &Map<u16>) -> Option<(usize, usize, u16)> {
+    claims
+        .iter_coords()
+        .fold(None, |best, ((x, y), &count)| match best {
+            Some((_, _, best_count)) if best_count >= count => best,
+            _ => Some((x, y, count)),
+        })
+}
+
+fn task_5(rectangles: &[Rectangle]) {
+    let claims = claim_counts(rectangles);
+    println!("overlapped area: {}", overlapped_area(&claims));
+    if let Some((x, y, count)) = most_contested(&claims) {
+        println!("most contested: {},{} with {} claims", x, y, count);
+    }
+}
+
+/// Builds a summed-area table of the cells claimed more than once.
+///
+/// The table is one larger than `claims` in each direction, so that
+/// entry `(x, y)` is the number of overlapped cells above and to the
+/// left of `(x, y)`, exclusive.
+fn summed_overlaps(claims: &Map<u16>) -> Map<u32> {
+    let mut table = Map::<u32>::new(claims.width + 1, claims.height + 1);
+    for y in 0..claims.height {
+        for x in 0..claims.width {
+            let overlapped = (claims[(x, y)] > 1) as u32;
+            table[(x + 1, y + 1)] =
+                overlapped + table[(x, y + 1)] + table[(x + 1, y)] - table[(x, y)];
+        }
+    }
+    table
+}
+
+/// Counts the overlapped cells within `rect` in constant time.
+fn overlaps_within(table: &Map<u32>, rect: &Rectangle) -> u32 {
+    table[(rect.right(), rect.bottom())] + table[(rect.left(), rect.top())]
+        - table[(rect.left(), rect.bottom())]
+        - table[(rect.right(), rect.top())]
+}
+
+/// Returns the ID of the first rectangle that overlaps no other.
+fn find_lonely_claim(rectangles: &[Rectangle]) -> Option<usize> {
+    let table = summed_overlaps(&claim_counts(rectangles));
+    rectangles
+        .iter()
+        .find(|rect| overlaps_within(&table, rect) == 0)
+        .map(|rect| rect.id)
+}
+
+fn task_6(rectangles: &[Rectangle]) {
+    println!("disjoint from all:");
+    if let Some(id) = find_lonely_claim(rectangles) {
+        println!("#{}", id);
+    }
 }
 
 fn main() {
@@ -182,3 +305,125 @@ fn main() {
     task_5(&rectangles);
     task_6(&rectangles);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_claims() -> Vec<Rectangle> {
+        ["#1 @ 1,3: 4x4", "#2 @ 3,1: 4x4", "#3 @ 5,5: 2x2"]
+            .iter()
+            .map(|line| line.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_claim_counts() {
+        let claims = example_claims();
+        let naive = claim_counts_naive(&claims);
+        let fast = claim_counts(&claims);
+        assert_eq!(fast.cells(), naive.cells());
+        assert_eq!(overlapped_area(&naive), 4);
+        assert_eq!(overlapped_area(&fast), 4);
+    }
+
+    #[test]
+    fn test_intersects() {
+        let left: Rectangle = "#1 @ 0,0: 2x2".parse().unwrap();
+        let right: Rectangle = "#2 @ 2,0: 2x2".parse().unwrap();
+        let below: Rectangle = "#3 @ 0,2: 2x2".parse().unwrap();
+        let across: Rectangle = "#4 @ 1,1: 2x2".parse().unwrap();
+        assert!(!left.intersects(&right));
+        assert!(!right.intersects(&left));
+        assert!(!left.intersects(&below));
+        assert!(left.intersects(&across));
+        assert!(across.intersects(&right));
+        assert!(across.intersects(&below));
+    }
+
+    #[test]
+    fn test_find_lonely_claim() {
+        let claims = example_claims();
+        assert_eq!(find_lonely_claim(&claims), Some(3));
+        let table = summed_overlaps(&claim_counts(&claims));
+        let overlaps = claims
+            .iter()
+            .map(|rect| overlaps_within(&table, rect))
+            .collect::<Vec<_>>();
+        assert_eq!(overlaps, [4, 4, 0]);
+    }
+
+    #[test]
+    fn test_most_contested() {
+        let claims = claim_counts(&example_claims());
+        let (x, y, count) = most_contested(&claims).unwrap();
+        assert_eq!(count, 2);
+        assert!((1..5).contains(&x) && (1..5).contains(&y));
+        assert_eq!((x, y), (3, 3));
+    }
+
+    #[test]
+    fn test_parse() {
+        let rect = "#123 @ 3,2: 5x4".parse::<Rectangle>();
+        let expected = Rectangle {
+            id: 123,
+            x: 3,
+            y: 2,
+            width: 5,
+            height: 4,
+        };
+        assert_eq!(rect, Ok(expected));
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        let lines = [
+            "",
+            "#1 1,3: 4x4",
+            "#1 @ 1,3: 44",
+            "#a @ 1,3: 4x4",
+            "1 @ 1,3: 4x4",
+            "#1 @ 1,3 4x4",
+            "#1 @ 13: 4x4",
+            "#1 @ 1,3: 4x4 extra",
+            "#1 @ 1,3:: 4x4",
+            "#1 @ 1,3: 4x-4",
+        ];
+        for line in lines.iter() {
+            assert_eq!(line.parse::<Rectangle>(), Err(NotARectangle), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_empty_input() {
+        let claims = claim_counts(&[]);
+        assert_eq!(overlapped_area(&claims), 0);
+        assert_eq!(most_contested(&claims), None);
+        assert_eq!(find_lonely_claim(&[]), None);
+        task_5(&[]);
+        task_6(&[]);
+    }
+
+    #[test]
+    fn test_iter_coords() {
+        let mut map = Map::<u16>::new(2, 2);
+        map[(1, 0)] = 1;
+        map[(0, 1)] = 2;
+        map[(1, 1)] = 3;
+        let cells = map.iter_coords().collect::<Vec<_>>();
+        let expected = [((0, 0), &0), ((1, 0), &1), ((0, 1), &2), ((1, 1), &3)];
+        assert_eq!(cells, expected);
+    }
+
+    #[test]
+    fn test_intersection_area() {
+        let claims = example_claims();
+        assert_eq!(claims[0].intersection_area(&claims[0]), 16);
+        assert_eq!(claims[0].intersection_area(&claims[1]), 4);
+        assert_eq!(claims[1].intersection_area(&claims[0]), 4);
+        assert_eq!(claims[0].intersection_area(&claims[2]), 0);
+        assert_eq!(claims[2].intersection_area(&claims[1]), 0);
+        let inner: Rectangle = "#4 @ 2,4: 2x1".parse().unwrap();
+        assert_eq!(claims[0].intersection_area(&inner), 2);
+    }
+}
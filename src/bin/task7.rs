@@ -1,5 +1,7 @@
 use std::{
+    cmp::Reverse,
     collections::HashMap,
+    env,
     fmt::{self, Display},
 };
 
@@ -10,6 +12,25 @@ struct Date {
     day: u8,
 }
 
+impl Date {
+    /// Counts the days since 0001-01-01 in the proleptic Gregorian
+    /// calendar.
+    fn days_since_epoch(self) -> i64 {
+        const DAYS_BEFORE_MONTH: [i64; 12] =
+            [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+        let year = i64::from(self.year);
+        let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let leap_days_before = (year - 1) / 4 - (year - 1) / 100 + (year - 1) / 400;
+        let leap_day = (is_leap_year && self.month > 2) as i64;
+        (year - 1) * 365
+            + leap_days_before
+            + DAYS_BEFORE_MONTH[self.month as usize - 1]
+            + leap_day
+            + i64::from(self.day)
+            - 1
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 struct Time {
     hour: u8,
@@ -30,12 +51,36 @@ impl Time {
     }
 }
 
+/// The start of a day.
+const MIDNIGHT: Time = Time { hour: 0, minute: 0 };
+
+/// The exclusive end of a day, used to cut sleep intervals that span
+/// midnight.
+const END_OF_DAY: Time = Time {
+    hour: 24,
+    minute: 0,
+};
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 struct Timestamp {
     date: Date,
     time: Time,
 }
 
+impl Timestamp {
+    /// Counts the minutes since 0001-01-01 00:00.
+    fn to_total_minutes(self) -> i64 {
+        self.date.days_since_epoch() * 24 * 60 + i64::from(self.time.as_minutes())
+    }
+
+    /// Returns how many minutes pass from `self` to `other`.
+    ///
+    /// The result is negative if `other` comes first.
+    fn minutes_between(self, other: Timestamp) -> i64 {
+        other.to_total_minutes() - self.to_total_minutes()
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 struct GuardId(u32);
 
@@ -60,81 +105,111 @@ struct Event {
 
 mod parsers {
     use super::{Date, Event, EventKind, GuardId, Time, Timestamp};
-    use nom::*;
-    use std::str::FromStr;
-
-    named!(date(&str) -> Date,
-        do_parse!(
-            year: map_res!(digit, u16::from_str) >>
-            char!('-') >>
-            month: map_res!(digit, u8::from_str) >>
-            char!('-') >>
-            day: map_res!(digit, u8::from_str) >>
-            (Date { year, month, day })
-        )
-    );
-
-    named!(time(&str) -> Time,
-        do_parse!(
-            hour: map_res!(digit, u8::from_str) >>
-            char!(':') >>
-            minute: map_res!(digit, u8::from_str) >>
-            (Time { hour, minute })
-        )
-    );
-
-    named!(timestamp(&str) -> Timestamp,
-        do_parse!(
-            char!('[') >>
-            date: date >>
-            char!(' ') >>
-            time: time >>
-            char!(']') >>
-            (Timestamp { date, time })
-        )
-    );
-
-    named!(guard_id(&str) -> GuardId,
-        do_parse!(
-            tag!("#") >>
-            id: map_res!(digit, u32::from_str) >>
-            (GuardId(id))
-        )
-    );
-
-    named!(event_kind(&str) -> EventKind,
-        alt!(
-            value!(EventKind::FallsAsleep, tag!("falls asleep"))
-            |
-            value!(EventKind::WakesUp, tag!("wakes up"))
-            |
-            map!(
-                delimited!(tag!("Guard "), guard_id, tag!(" begins shift")),
-                EventKind::ShiftBegin
-            )
-        )
-    );
-
-    named!(event(&str) -> Event,
-        do_parse!(
-            stamp: timestamp >>
-            char!(' ') >>
-            kind: event_kind >>
-            (Event {stamp, kind})
-        )
-    );
-
-    #[derive(Debug, Clone)]
+    use std::{num::ParseIntError, str::FromStr};
+
+    /// Splits `s` at the first occurrence of `sep`.
+    fn split_pair(s: &str, sep: char) -> Result<(&str, &str), ParseEventError> {
+        let mut parts = s.splitn(2, sep);
+        match (parts.next(), parts.next()) {
+            (Some(left), Some(right)) => Ok((left, right)),
+            _ => Err(ParseEventError),
+        }
+    }
+
+    /// Parses `yyyy-mm-dd`.
+    fn date(s: &str) -> Result<Date, ParseEventError> {
+        let (year, s) = split_pair(s, '-')?;
+        let (month, day) = split_pair(s, '-')?;
+        let date = Date {
+            year: year.parse()?,
+            month: month.parse()?,
+            day: day.parse()?,
+        };
+        if !(1..=12).contains(&date.month) || !(1..=31).contains(&date.day) {
+            return Err(ParseEventError);
+        }
+        Ok(date)
+    }
+
+    /// Parses `hh:mm`.
+    fn time(s: &str) -> Result<Time, ParseEventError> {
+        let (hour, minute) = split_pair(s, ':')?;
+        let time = Time {
+            hour: hour.parse()?,
+            minute: minute.parse()?,
+        };
+        if time.hour > 23 || time.minute > 59 {
+            return Err(ParseEventError);
+        }
+        Ok(time)
+    }
+
+    /// Parses `[yyyy-mm-dd hh:mm]`.
+    fn timestamp(s: &str) -> Result<Timestamp, ParseEventError> {
+        let s = match split_pair(s, '[')? {
+            ("", s) => s,
+            _ => return Err(ParseEventError),
+        };
+        let s = match split_pair(s, ']')? {
+            (s, "") => s,
+            _ => return Err(ParseEventError),
+        };
+        let (day, clock) = split_pair(s, ' ')?;
+        let stamp = Timestamp {
+            date: date(day)?,
+            time: time(clock)?,
+        };
+        Ok(stamp)
+    }
+
+    fn event_kind(s: &str) -> Result<EventKind, ParseEventError> {
+        match s {
+            "falls asleep" => Ok(EventKind::FallsAsleep),
+            "wakes up" => Ok(EventKind::WakesUp),
+            _ => {
+                let id = s
+                    .strip_prefix("Guard ")
+                    .and_then(|s| s.strip_suffix(" begins shift"))
+                    .ok_or(ParseEventError)?;
+                Ok(EventKind::ShiftBegin(id.parse()?))
+            },
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
     pub struct ParseEventError;
 
+    impl From<ParseIntError> for ParseEventError {
+        fn from(_: ParseIntError) -> Self {
+            ParseEventError
+        }
+    }
+
+    impl FromStr for GuardId {
+        type Err = ParseEventError;
+
+        /// Parses the format written by `Display`, e.g. `#123`.
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let id = s.strip_prefix('#').ok_or(ParseEventError)?;
+            if !id.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ParseEventError);
+            }
+            Ok(GuardId(id.parse()?))
+        }
+    }
+
     impl FromStr for Event {
         type Err = ParseEventError;
 
+        /// Parses a line of the form `[yyyy-mm-dd hh:mm] event`.
         fn from_str(input: &str) -> Result<Self, Self::Err> {
-            match event(input) {
-                Ok(("", event)) => Ok(event),
-                _ => Err(ParseEventError),
-            }
+            let pos = input.find("] ").ok_or(ParseEventError)?;
+            let (stamp, kind) = (&input[..=pos], &input[pos + 2..]);
+            let event = Event {
+                stamp: timestamp(stamp)?,
+                kind: event_kind(kind)?,
+            };
+            Ok(event)
         }
     }
 }
@@ -145,14 +220,23 @@ enum ScheduleError {
     MissingGuard,
     DoubleSleep,
     MissingSleep,
-    SleepAtMidnight,
+    SleepTooLong,
 }
 
+/// Maps each guard to the intervals during which they slept.
+///
+/// Guards who never slept are included with no intervals.
 type Schedule = HashMap<GuardId, Vec<(Time, Time)>>;
 
+/// Collects the sleep intervals of each guard.
+///
+/// The events may come in any order; they are sorted by timestamp
+/// first.
 fn build_sleep_schedules(events: &[Event]) -> Result<Schedule, ScheduleError> {
     use boolinator::Boolinator;
 
+    let mut events = events.iter().collect::<Vec<_>>();
+    events.sort_by_key(|event| event.stamp);
     let mut schedule = Schedule::new();
     let mut current_guard = None;
     let mut sleep_begin = None;
@@ -161,95 +245,345 @@ fn build_sleep_schedules(events: &[Event]) -> Result<Schedule, ScheduleError> {
             EventKind::ShiftBegin(id) => {
                 sleep_begin
                     .is_none()
-                    .ok_or(ScheduleError::ShiftChangeWhileSleep)?;
+                    .as_result((), ScheduleError::ShiftChangeWhileSleep)?;
+                schedule.entry(id).or_default();
                 current_guard = Some(id);
             },
             EventKind::FallsAsleep => {
-                current_guard.is_some().ok_or(ScheduleError::MissingGuard)?;
-                sleep_begin.is_none().ok_or(ScheduleError::DoubleSleep)?;
+                current_guard
+                    .is_some()
+                    .as_result((), ScheduleError::MissingGuard)?;
+                sleep_begin
+                    .is_none()
+                    .as_result((), ScheduleError::DoubleSleep)?;
                 sleep_begin = Some(event.stamp);
             },
             EventKind::WakesUp => {
                 let id = current_guard.ok_or(ScheduleError::MissingGuard)?;
                 let sleep_begin = sleep_begin.take().ok_or(ScheduleError::MissingSleep)?;
-                (sleep_begin.date == event.stamp.date).ok_or(ScheduleError::SleepAtMidnight)?;
-                schedule
-                    .entry(id)
-                    .or_default()
-                    .push((sleep_begin.time, event.stamp.time))
+                let intervals = schedule.entry(id).or_default();
+                let end_of_day = Timestamp {
+                    date: sleep_begin.date,
+                    time: END_OF_DAY,
+                };
+                let duration = sleep_begin.minutes_between(event.stamp);
+                let until_midnight = sleep_begin.minutes_between(end_of_day);
+                if duration < until_midnight {
+                    intervals.push((sleep_begin.time, event.stamp.time));
+                } else if duration < until_midnight + 24 * 60 {
+                    // Split the interval at midnight.
+                    intervals.push((sleep_begin.time, END_OF_DAY));
+                    if duration > until_midnight {
+                        intervals.push((MIDNIGHT, event.stamp.time));
+                    }
+                } else {
+                    return Err(ScheduleError::SleepTooLong);
+                }
             },
         }
     }
     Ok(schedule)
 }
 
+/// Counts how often the guard slept during each minute of the midnight
+/// hour.
+///
+/// Sleep outside of the midnight hour, e.g. before midnight, is ignored.
 fn tally_sleep_minutes(sleep_intervals: &[(Time, Time)]) -> [u16; 60] {
     let mut tally = [0; 60];
-    let common_hour = match sleep_intervals.first() {
-        Some(&(Time { hour, .. }, Time { .. })) => hour,
-        None => return tally,
-    };
     for &(begin, end) in sleep_intervals {
-        assert!(begin.hour == common_hour);
-        assert!(end.hour == common_hour);
-        for minute in begin.minute..end.minute {
+        for minute in begin.as_minutes()..end.as_minutes().min(60) {
             tally[minute as usize] += 1;
         }
     }
     tally
 }
 
-fn strategy1(schedule: &Schedule) {
-    let guard = schedule
-        .iter()
-        .max_by_key(|(_, times)| {
-            times
-                .iter()
-                .cloned()
-                .map(|(begin, end)| end.minutes_since(begin).unwrap() as u32)
-                .sum::<u32>()
-        })
-        .map(|(id, _)| id)
-        .unwrap();
-    let minute = tally_sleep_minutes(&schedule[guard])
+/// Returns the minute during which the guard slept most often, and how
+/// often they slept during it.
+fn find_sleepiest_minute(sleep_intervals: &[(Time, Time)]) -> (u8, u32) {
+    tally_sleep_minutes(sleep_intervals)
         .iter()
         .cloned()
         .enumerate()
         .max_by_key(|&(_, count)| count)
-        .map(|(i, _)| i as u8)
-        .unwrap();
-    println!("strategy 1: {}", guard.0 * minute as u32);
+        .map(|(i, count)| (i as u8, count as u32))
+        .unwrap()
 }
 
-fn strategy2(schedule: &Schedule) {
-    let (guard, minute) = schedule
+/// Returns each guard's total minutes asleep, sleepiest guard first.
+///
+/// Guards with the same total are sorted by ID.
+fn total_sleep(schedule: &Schedule) -> Vec<(GuardId, u32)> {
+    let mut totals = schedule
         .iter()
-        .map(|(guard, times)| {
-            let (minute, count) = tally_sleep_minutes(times)
+        .map(|(&guard, times)| {
+            let total = times
                 .iter()
                 .cloned()
-                .enumerate()
-                .max_by_key(|&(_, count)| count)
-                .unwrap();
+                .map(|(begin, end)| end.minutes_since(begin).unwrap() as u32)
+                .sum::<u32>();
+            (guard, total)
+        })
+        .collect::<Vec<_>>();
+    totals.sort_by_key(|&(guard, total)| (Reverse(total), guard));
+    totals
+}
+
+fn strategy1(schedule: &Schedule) {
+    let (guard, _) = total_sleep(schedule)[0];
+    let (minute, _) = find_sleepiest_minute(&schedule[&guard]);
+    println!("strategy 1: {}", guard.0 * minute as u32);
+}
+
+/// Finds the guard who is most frequently asleep on the same minute.
+///
+/// Returns that guard and the minute.
+fn strategy_two(schedule: &Schedule) -> (GuardId, u8) {
+    schedule
+        .iter()
+        .map(|(&guard, times)| {
+            let (minute, count) = find_sleepiest_minute(times);
             (guard, minute, count)
         })
         .max_by_key(|&(_, _, count)| count)
         .map(|(guard, minute, _)| (guard, minute))
-        .unwrap();
-    println!("strategy 2: {}", guard.0 * minute as u32);
+        .unwrap()
 }
 
 fn main() {
     use std::io::{self, BufRead};
     let stdin = io::stdin();
-    let mut events = stdin
+    let events = stdin
         .lock()
         .lines()
         .map(|line| line.unwrap().trim().parse())
         .collect::<Result<Vec<Event>, _>>()
         .unwrap();
-    events.sort_by_key(|event| event.stamp);
     let schedule = build_sleep_schedules(&events).unwrap();
+    if env::args().skip(1).any(|arg| arg == "--summary") {
+        for (guard, total) in total_sleep(&schedule) {
+            println!("{}: {} minutes asleep", guard, total);
+        }
+    }
     strategy1(&schedule);
-    strategy2(&schedule);
+    let (guard, minute) = strategy_two(&schedule);
+    println!("strategy 2: {}", guard.0 * minute as u32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u8, minute: u8) -> Time {
+        Time { hour, minute }
+    }
+
+    fn example_schedule() -> Schedule {
+        let mut schedule = Schedule::new();
+        schedule.insert(
+            GuardId(10),
+            vec![
+                (time(0, 5), time(0, 25)),
+                (time(0, 30), time(0, 55)),
+                (time(0, 24), time(0, 29)),
+            ],
+        );
+        schedule.insert(
+            GuardId(99),
+            vec![
+                (time(0, 40), time(0, 50)),
+                (time(0, 36), time(0, 46)),
+                (time(0, 45), time(0, 55)),
+            ],
+        );
+        schedule
+    }
+
+    #[test]
+    fn test_strategy_two() {
+        let schedule = example_schedule();
+        assert_eq!(find_sleepiest_minute(&schedule[&GuardId(10)]), (24, 2));
+        assert_eq!(strategy_two(&schedule), (GuardId(99), 45));
+    }
+
+    #[test]
+    fn test_sleep_across_midnight() {
+        let events = [
+            "[1518-11-01 23:50] Guard #10 begins shift",
+            "[1518-11-01 23:58] falls asleep",
+            "[1518-11-02 00:05] wakes up",
+        ]
+        .iter()
+        .map(|line| line.parse().unwrap())
+        .collect::<Vec<Event>>();
+        let schedule = build_sleep_schedules(&events).unwrap();
+        let intervals = &schedule[&GuardId(10)];
+        assert_eq!(
+            intervals,
+            &[(time(23, 58), END_OF_DAY), (MIDNIGHT, time(0, 5))]
+        );
+        let tally = tally_sleep_minutes(intervals);
+        let asleep = (0..60).filter(|&m| tally[m] > 0).collect::<Vec<_>>();
+        assert_eq!(asleep, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_sleep_until_midnight() {
+        let schedule = |lines: &[&str]| {
+            let events = lines
+                .iter()
+                .map(|line| line.parse().unwrap())
+                .collect::<Vec<Event>>();
+            build_sleep_schedules(&events)
+        };
+        let until_midnight = schedule(&[
+            "[1518-11-01 23:50] Guard #10 begins shift",
+            "[1518-11-01 23:58] falls asleep",
+            "[1518-11-02 00:00] wakes up",
+        ])
+        .unwrap();
+        assert_eq!(until_midnight[&GuardId(10)], [(time(23, 58), END_OF_DAY)]);
+        let too_long = schedule(&[
+            "[1518-11-01 23:50] Guard #10 begins shift",
+            "[1518-11-01 23:58] falls asleep",
+            "[1518-11-03 00:00] wakes up",
+        ]);
+        assert!(matches!(too_long, Err(ScheduleError::SleepTooLong)));
+    }
+
+    #[test]
+    fn test_total_sleep() {
+        let totals = total_sleep(&example_schedule());
+        assert_eq!(totals, [(GuardId(10), 50), (GuardId(99), 30)]);
+    }
+
+    #[test]
+    fn test_parse_event() {
+        let stamp = Timestamp {
+            date: Date {
+                year: 1518,
+                month: 11,
+                day: 1,
+            },
+            time: time(23, 58),
+        };
+        let parse = |line: &str| line.parse::<Event>();
+        let event = |kind| Ok(Event { stamp, kind });
+        assert_eq!(
+            parse("[1518-11-01 23:58] Guard #99 begins shift"),
+            event(EventKind::ShiftBegin(GuardId(99)))
+        );
+        assert_eq!(
+            parse("[1518-11-01 23:58] falls asleep"),
+            event(EventKind::FallsAsleep)
+        );
+        assert_eq!(
+            parse("[1518-11-01 23:58] wakes up"),
+            event(EventKind::WakesUp)
+        );
+        let malformed = [
+            "",
+            "[1518-11-01 23:58]",
+            "1518-11-01 23:58] wakes up",
+            "[1518-11-01 23:58] wakes",
+            "[1518-11-01] wakes up",
+            "[1518-11-01 23-58] wakes up",
+            "[1518-11-01 23:58] Guard 99 begins shift",
+            "[1518-11-01 23:58] Guard #x begins shift",
+            "[1518-13-01 00:25] wakes up",
+            "[1518-00-01 00:25] wakes up",
+            "[1518-11-00 00:25] wakes up",
+            "[1518-11-32 00:25] wakes up",
+            "[1518-11-01 24:00] wakes up",
+            "[1518-11-01 00:60] wakes up",
+        ];
+        for line in malformed.iter() {
+            assert_eq!(parse(line), Err(parsers::ParseEventError), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_unsorted_events() {
+        let events = [
+            "[1518-11-01 00:00] Guard #10 begins shift",
+            "[1518-11-01 00:05] falls asleep",
+            "[1518-11-01 00:25] wakes up",
+            "[1518-11-01 23:58] Guard #99 begins shift",
+            "[1518-11-02 00:40] falls asleep",
+            "[1518-11-02 00:50] wakes up",
+        ]
+        .iter()
+        .map(|line| line.parse().unwrap())
+        .collect::<Vec<Event>>();
+        let shuffled = [4, 1, 5, 0, 3, 2]
+            .iter()
+            .map(|&i| events[i].clone())
+            .collect::<Vec<_>>();
+        let schedule = build_sleep_schedules(&shuffled).unwrap();
+        assert_eq!(schedule, build_sleep_schedules(&events).unwrap());
+        assert_eq!(schedule[&GuardId(10)], [(time(0, 5), time(0, 25))]);
+        assert_eq!(schedule[&GuardId(99)], [(time(0, 40), time(0, 50))]);
+    }
+
+    #[test]
+    fn test_find_sleepiest_minute() {
+        let intervals = [
+            (time(0, 10), time(0, 20)),
+            (time(0, 15), time(0, 30)),
+            (time(0, 18), time(0, 19)),
+            (time(0, 40), time(0, 45)),
+        ];
+        assert_eq!(find_sleepiest_minute(&intervals), (18, 3));
+        assert_eq!(find_sleepiest_minute(&intervals[3..]).1, 1);
+    }
+
+    #[test]
+    fn test_minutes_between() {
+        let stamp = |line: &str| line.parse::<Event>().unwrap().stamp;
+        let begin = stamp("[1518-11-01 23:58] falls asleep");
+        let same_day = stamp("[1518-11-01 23:59] wakes up");
+        let next_day = stamp("[1518-11-02 00:05] wakes up");
+        let next_month = stamp("[1518-12-01 00:05] wakes up");
+        let next_year = stamp("[1519-01-01 23:58] wakes up");
+        assert_eq!(begin.minutes_between(same_day), 1);
+        assert_eq!(begin.minutes_between(next_day), 7);
+        assert_eq!(next_day.minutes_between(begin), -7);
+        assert_eq!(begin.minutes_between(next_month), 29 * 24 * 60 + 7);
+        assert_eq!(begin.minutes_between(next_year), 61 * 24 * 60);
+    }
+
+    #[test]
+    fn test_guard_without_sleep() {
+        let events = [
+            "[1518-11-01 00:00] Guard #10 begins shift",
+            "[1518-11-01 00:05] falls asleep",
+            "[1518-11-01 00:25] wakes up",
+            "[1518-11-02 00:00] Guard #99 begins shift",
+        ]
+        .iter()
+        .map(|line| line.parse().unwrap())
+        .collect::<Vec<Event>>();
+        let schedule = build_sleep_schedules(&events).unwrap();
+        assert_eq!(
+            total_sleep(&schedule),
+            [(GuardId(10), 20), (GuardId(99), 0)]
+        );
+        assert_eq!(strategy_two(&schedule).0, GuardId(10));
+    }
+
+    #[test]
+    fn test_guard_id_round_trip() {
+        for &id in [GuardId(0), GuardId(99), GuardId(u32::MAX)].iter() {
+            assert_eq!(format!("{}", id).parse::<GuardId>(), Ok(id));
+        }
+        for s in ["", "#", "99", "# 99", "#+99", "#-1", "#99a", "##99"].iter() {
+            assert_eq!(
+                s.parse::<GuardId>(),
+                Err(parsers::ParseEventError),
+                "{:?}",
+                s
+            );
+        }
+    }
 }
@@ -1,10 +1,18 @@
 use std::{
+    env,
     io::{self, Read},
     ops::Range,
+    sync::Arc,
+    thread,
 };
 
+/// Returns true if the units are the same letter with opposite case.
+///
+/// Bytes that aren't ASCII letters never annihilate.
 fn annihilible(left: u8, right: u8) -> bool {
-    left != right && (left.to_ascii_lowercase() == right || left == right.to_ascii_lowercase())
+    left.is_ascii_alphabetic()
+        && left != right
+        && (left.to_ascii_lowercase() == right || left == right.to_ascii_lowercase())
 }
 
 fn find_maximally_reducible_sequence(polymer: &[u8], pos: usize) -> Option<Range<usize>> {
@@ -16,6 +24,7 @@ fn find_maximally_reducible_sequence(polymer: &[u8], pos: usize) -> Option<Range
         .map(|(l, r)| l..r + 1)
 }
 
+#[allow(dead_code)]
 fn reduce_polymer(polymer: &mut Vec<u8>) {
     let mut i = 0;
     while i < polymer.len() {
@@ -28,33 +37,155 @@ fn reduce_polymer(polymer: &mut Vec<u8>) {
     }
 }
 
-fn to_reduced(mut polymer: Vec<u8>) -> Vec<u8> {
-    reduce_polymer(&mut polymer);
-    polymer
+/// Like `reduce_polymer`, but in linear time.
+///
+/// Each unit is pushed onto a stack of survivors and annihilates with
+/// the top of the stack if possible. Unlike `Vec::drain`, this never
+/// shifts the rest of the polymer around.
+///
+/// Two units react if `annihilates` returns true for them; pass
+/// `annihilible` to get the regular rules.
+fn reduce_polymer_stack(polymer: &[u8], annihilates: impl Fn(u8, u8) -> bool) -> Vec<u8> {
+    let mut survivors = Vec::with_capacity(polymer.len());
+    push_units(polymer.iter().cloned(), annihilates, &mut survivors);
+    survivors
 }
 
-fn main() {
-    let contents = {
-        let mut contents = Vec::new();
-        let stdin = io::stdin();
-        stdin.lock().read_to_end(&mut contents).unwrap();
-        while let Some(b'\n') = contents.last() {
-            contents.pop();
+/// Pushes each unit onto the stack of `survivors`, popping the top of
+/// the stack instead if the two annihilate.
+fn push_units<I>(units: I, annihilates: impl Fn(u8, u8) -> bool, survivors: &mut Vec<u8>)
+where
+    I: IntoIterator<Item = u8>,
+{
+    for unit in units {
+        match survivors.last() {
+            Some(&top) if annihilates(top, unit) => {
+                survivors.pop();
+            },
+            _ => survivors.push(unit),
         }
-        contents
-    };
+    }
+}
+
+/// Like `reduce_polymer_stack`, but takes the units one by one, e.g.
+/// straight from a reader.
+#[allow(dead_code)]
+fn reduce_stream<I: Iterator<Item = u8>>(bytes: I) -> Vec<u8> {
+    let mut survivors = Vec::new();
+    push_units(bytes, annihilible, &mut survivors);
+    survivors
+}
+
+/// Removes all units of type `dropped` and reduces the rest into
+/// `scratch`.
+///
+/// `dropped` must be lowercase. Any previous contents of `scratch` are
+/// discarded, but its allocation is reused. Returns the length of the
+/// reduced polymer.
+fn reduce_into(input: &[u8], dropped: u8, scratch: &mut Vec<u8>) -> usize {
+    scratch.clear();
+    let units = input
+        .iter()
+        .cloned()
+        .filter(|unit| unit.to_ascii_lowercase() != dropped);
+    push_units(units, annihilible, scratch);
+    scratch.len()
+}
+
+/// Reduces the polymer with the regular rules.
+#[allow(dead_code)]
+fn reduced_polymer(polymer: Vec<u8>) -> Vec<u8> {
+    reduce_polymer_stack(&polymer, annihilible)
+}
+
+/// Reduces the polymer and returns it as text.
+fn reduced_string(input: &[u8]) -> String {
+    String::from_utf8_lossy(&reduce_polymer_stack(input, annihilible)).into_owned()
+}
+
+/// Returns the length of the fully reduced polymer.
+fn reduced_len(polymer: &[u8]) -> usize {
+    reduce_polymer_stack(polymer, annihilible).len()
+}
+
+/// Counts the units of each type, regardless of their polarity.
+#[allow(dead_code)]
+fn unit_histogram(polymer: &[u8]) -> [usize; 26] {
+    let mut histogram = [0; 26];
+    for &unit in polymer {
+        if unit.is_ascii_alphabetic() {
+            histogram[(unit.to_ascii_lowercase() - b'a') as usize] += 1;
+        }
+    }
+    histogram
+}
+
+/// Finds the unit type whose removal permits the shortest reduction.
+///
+/// Returns the lowercase unit and the length of the reduced polymer.
+#[allow(dead_code)]
+fn best_removal(polymer: &[u8]) -> (u8, usize) {
+    let mut scratch = Vec::with_capacity(polymer.len());
+    (b'a'..=b'z')
+        .map(|dropped| (dropped, reduce_into(polymer, dropped, &mut scratch)))
+        .min_by_key(|&(_, len)| len)
+        .unwrap()
+}
+
+/// Like `best_removal`, but splits the unit types among `num_threads`
+/// threads.
+///
+/// Ties are broken the same way as in `best_removal`.
+fn best_removal_parallel(polymer: &[u8], num_threads: usize) -> (u8, usize) {
+    let polymer = Arc::new(polymer.to_owned());
+    let units = (b'a'..=b'z').collect::<Vec<u8>>();
+    let chunk_size = units.len().div_ceil(num_threads.max(1));
+    let handles = units
+        .chunks(chunk_size)
+        .map(|chunk| {
+            let chunk = chunk.to_owned();
+            let polymer = Arc::clone(&polymer);
+            thread::spawn(move || {
+                let mut scratch = Vec::with_capacity(polymer.len());
+                chunk
+                    .into_iter()
+                    .map(|dropped| (dropped, reduce_into(&polymer, dropped, &mut scratch)))
+                    .min_by_key(|&(_, len)| len)
+                    .unwrap()
+            })
+        })
+        .collect::<Vec<_>>();
+    handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .min_by_key(|&(_, len)| len)
+        .unwrap()
+}
+
+/// Reads the polymer and drops anything that isn't a unit, e.g. the
+/// trailing newline.
+fn read_polymer<R: Read>(mut reader: R) -> io::Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    reader.read_to_end(&mut contents)?;
+    contents.retain(u8::is_ascii_alphabetic);
+    Ok(contents)
+}
+
+fn main() {
+    let stdin = io::stdin();
+    let contents = read_polymer(stdin.lock()).unwrap();
     // Task 9.
-    println!("simple reduction: {}", to_reduced(contents.clone()).len());
+    println!("simple reduction: {}", reduced_len(&contents));
+    if env::args().skip(1).any(|arg| arg == "--show") {
+        println!("{}", reduced_string(&contents));
+    }
     // Task 10.
-    let shortest = (b'a'..=b'z')
-        .map(|dropped| {
-            let mut contents = contents.clone();
-            contents.retain(|c| c.to_ascii_lowercase() != dropped);
-            to_reduced(contents).len()
-        })
-        .min()
-        .unwrap();
-    println!("optimal reduction: {}", shortest);
+    let num_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let (dropped, shortest) = best_removal_parallel(&contents, num_threads);
+    println!(
+        "optimal reduction: {} (removed {})",
+        shortest, dropped as char
+    );
 }
 
 #[cfg(test)]
@@ -75,4 +206,112 @@ mod tests {
         assert_reduce(b"aabAAB", b"aabAAB");
         assert_reduce(b"dabAcCaCBAcCcaDA", b"dabCBAcaDA");
     }
+
+    #[test]
+    fn test_stack() {
+        let polymers: &[&[u8]] = &[b"aA", b"abBA", b"abAB", b"aabAAB", b"dabAcCaCBAcCcaDA"];
+        for &polymer in polymers {
+            let mut expected = polymer.to_owned();
+            reduce_polymer(&mut expected);
+            assert_eq!(reduce_polymer_stack(polymer, annihilible), expected);
+        }
+    }
+
+    #[test]
+    fn test_custom_rule() {
+        let is_vowel = |c| b"aeiou".contains(&c);
+        let vowels_annihilate = |l, r| l != r && is_vowel(l) && is_vowel(r);
+        assert_eq!(reduce_polymer_stack(b"xaeiy", vowels_annihilate), b"xiy");
+        assert_eq!(reduce_polymer_stack(b"aaeex", vowels_annihilate), b"x");
+        assert_eq!(reduce_polymer_stack(b"aA", vowels_annihilate), b"aA");
+    }
+
+    #[test]
+    fn test_reduced_polymer() {
+        let polymer = b"dabAcCaCBAcCcaDA".to_vec();
+        assert_eq!(reduced_polymer(polymer.clone()), b"dabCBAcaDA");
+        assert_eq!(reduced_len(&polymer), 10);
+    }
+
+    #[test]
+    fn test_best_removal() {
+        assert_eq!(best_removal(b"dabAcCaCBAcCcaDA"), (b'c', 4));
+    }
+
+    #[test]
+    fn test_best_removal_parallel() {
+        let polymer = b"dabAcCaCBAcCcaDA";
+        for num_threads in 1..=30 {
+            assert_eq!(best_removal_parallel(polymer, num_threads), (b'c', 4));
+        }
+        assert_eq!(best_removal_parallel(b"", 4), best_removal(b""));
+    }
+
+    #[test]
+    fn test_unit_histogram() {
+        let histogram = unit_histogram(b"dabAcCaCBAcCcaDA");
+        assert_eq!(histogram[..4], [6, 2, 6, 2]);
+        assert!(histogram[4..].iter().all(|&count| count == 0));
+    }
+
+    #[test]
+    fn test_reduced_string() {
+        assert_eq!(reduced_string(b"abBA"), "");
+        assert_eq!(reduced_string(b"dabAcCaCBAcCcaDA"), "dabCBAcaDA");
+    }
+
+    #[test]
+    fn test_non_letters() {
+        assert!(!annihilible(b'1', b'1'));
+        assert!(!annihilible(b'@', b'`'));
+        assert!(!annihilible(b'[', b'{'));
+        // A non-letter separates units that would otherwise react.
+        assert_eq!(reduced_string(b"a1A"), "a1A");
+        assert_eq!(reduced_string(b"ab1BA"), "ab1BA");
+        assert_eq!(reduced_string(b"1aA1"), "11");
+    }
+
+    #[test]
+    fn test_reduce_into() {
+        let polymer = b"dabAcCaCBAcCcaDA";
+        let mut scratch = Vec::new();
+        for dropped in b'a'..=b'z' {
+            let mut expected = polymer.to_vec();
+            expected.retain(|c| c.to_ascii_lowercase() != dropped);
+            let expected = reduced_polymer(expected);
+            assert_eq!(reduce_into(polymer, dropped, &mut scratch), expected.len());
+            assert_eq!(scratch, expected);
+        }
+    }
+
+    #[test]
+    fn test_fully_annihilating() {
+        let polymers: &[&[u8]] = &[b"aA", b"abBA", b"aAbBcC", b""];
+        for &polymer in polymers {
+            assert_reduce(polymer, b"");
+            assert_eq!(reduce_polymer_stack(polymer, annihilible), b"");
+            assert_eq!(reduced_len(polymer), 0);
+        }
+        assert_eq!(best_removal(b""), (b'a', 0));
+    }
+
+    #[test]
+    fn test_read_polymer() {
+        assert_eq!(
+            read_polymer(&b"dabAcCaCBAcCcaDA\n"[..]).unwrap(),
+            b"dabAcCaCBAcCcaDA"
+        );
+        assert_eq!(read_polymer(&b"\n\n\n"[..]).unwrap(), b"");
+        assert_eq!(read_polymer(&b""[..]).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_reduce_stream() {
+        let polymer = b"dabAcCaCBAcCcaDA";
+        let expected = reduced_polymer(polymer.to_vec());
+        assert_eq!(reduce_stream(polymer.iter().cloned()), expected);
+        let reader = io::BufReader::new(&polymer[..]);
+        let bytes = reader.bytes().map(Result::unwrap);
+        assert_eq!(reduce_stream(bytes), expected);
+    }
 }
@@ -0,0 +1,101 @@
+//! Code shared between several tasks.
+
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    io::{self, BufRead},
+    num::ParseIntError,
+};
+
+/// Error that occurs while reading a list of frequency steps.
+#[derive(Debug)]
+pub enum ParseStepError {
+    Io(io::Error),
+    BadStep {
+        line_number: usize,
+        line: String,
+        error: ParseIntError,
+    },
+}
+
+impl Display for ParseStepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseStepError::Io(err) => write!(f, "could not read input: {}", err),
+            ParseStepError::BadStep {
+                line_number,
+                line,
+                error,
+            } => write!(f, "line {}: bad step {:?}: {}", line_number, line, error),
+        }
+    }
+}
+
+impl Error for ParseStepError {}
+
+impl From<io::Error> for ParseStepError {
+    fn from(err: io::Error) -> Self {
+        ParseStepError::Io(err)
+    }
+}
+
+/// Parses a single frequency step like `+3` or `-2`.
+///
+/// Surrounding whitespace is ignored and a single leading sign is
+/// accepted, so `++1` is an error.
+pub fn parse_step(s: &str) -> Result<i32, ParseIntError> {
+    s.trim().parse()
+}
+
+/// Parses one frequency step per line.
+///
+/// Blank lines are skipped. Line numbers in the error start at one.
+pub fn parse_steps<R: BufRead>(r: R) -> Result<Vec<i32>, ParseStepError> {
+    let mut steps = Vec::new();
+    for (i, line) in r.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_step(&line) {
+            Ok(step) => steps.push(step),
+            Err(error) => {
+                let line_number = i + 1;
+                return Err(ParseStepError::BadStep {
+                    line_number,
+                    line,
+                    error,
+                });
+            },
+        }
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_step() {
+        assert_eq!(parse_step("+3"), Ok(3));
+        assert_eq!(parse_step("-2"), Ok(-2));
+        assert_eq!(parse_step(" +7\r"), Ok(7));
+        assert!(parse_step("++1").is_err());
+    }
+
+    #[test]
+    fn test_parse_steps() {
+        let steps = parse_steps("+3\n-2\n".as_bytes()).unwrap();
+        assert_eq!(steps, [3, -2]);
+        match parse_steps("+3\n++1\n-2\n".as_bytes()) {
+            Err(ParseStepError::BadStep {
+                line_number, line, ..
+            }) => {
+                assert_eq!(line_number, 2);
+                assert_eq!(line, "++1");
+            },
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}